@@ -0,0 +1,11 @@
+use std::error::Error;
+use vergen_gitcl::{Emitter, Gitcl};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // The user agent sent to Tachi includes the git version
+    let gitcl = Gitcl::builder().describe(false, false, None).build();
+
+    Emitter::default().add_instructions(&gitcl)?.emit()?;
+
+    Ok(())
+}
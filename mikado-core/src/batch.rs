@@ -0,0 +1,84 @@
+use crate::types::GameVersion;
+use crate::types::tachi::{Import, ImportMeta};
+use crate::types::user::User;
+use anyhow::Result;
+use log::{debug, info};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Where BATCH-MANUAL files are written when live submission is disabled.
+const BATCH_DIRECTORY: &str = "mikado-batch";
+
+/// Adds the import to the BATCH-MANUAL file of the user's current session.
+///
+/// A new file is started every time a card is scanned, so that each one can be uploaded to Tachi
+/// on its own.
+pub fn append(user: &User, import: Import) -> Result<()> {
+    std::fs::create_dir_all(BATCH_DIRECTORY)
+        .map_err(|err| anyhow::anyhow!("Could not create {BATCH_DIRECTORY} folder: {}", err))?;
+
+    let path = Path::new(BATCH_DIRECTORY).join(format!(
+        "{}-{}.json",
+        user.card_id,
+        user.session_started.format("%Y%m%d-%H%M%S")
+    ));
+
+    let mut batch = if path.exists() {
+        let file = File::open(&path)
+            .map_err(|err| anyhow::anyhow!("Could not open {}: {}", path.display(), err))?;
+        serde_json::from_reader::<_, Import>(BufReader::new(file))
+            .map_err(|err| anyhow::anyhow!("Could not parse {}: {}", path.display(), err))?
+    } else {
+        let version = GameVersion::from_tachi_id(&import.meta.version).unwrap_or_default();
+        Import {
+            meta: ImportMeta::batch_manual(version),
+            classes: None,
+            scores: vec![],
+        }
+    };
+
+    if import.classes.is_some() {
+        batch.classes = import.classes;
+    }
+
+    let mut added = 0;
+    for score in import.scores {
+        // The same play can be handed over more than once, for example when it is replayed
+        let duplicate = batch.scores.iter().any(|existing| {
+            existing.identifier == score.identifier
+                && existing.difficulty == score.difficulty
+                && existing.time_achieved == score.time_achieved
+                && existing.score == score.score
+        });
+        if duplicate {
+            debug!(
+                "Skipping duplicate score on chart {} in {}",
+                score.identifier,
+                path.display()
+            );
+            continue;
+        }
+
+        batch.scores.push(score);
+        added += 1;
+    }
+
+    // Write to a temporary file first so a crash mid-write never corrupts the session file
+    let temporary_path = path.with_extension("json.tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        serde_json::to_writer_pretty(&mut writer, &batch)?;
+        writer.flush()?;
+    }
+    std::fs::rename(&temporary_path, &path)?;
+
+    info!(
+        "Saved {added} score(s) for card {} to {} ({} in this session)",
+        user.card_id,
+        path.display(),
+        batch.scores.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use crate::property::PropertyApi;
+use anyhow::Result;
+use bytes::Bytes;
+use kbinxml::{Node, Value};
+use log::{debug, error};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, RwLock};
+
+/// Where the intercepted traffic is written when capture is enabled.
+const CAPTURE_DIRECTORY: &str = "mikado-capture";
+
+/// Attributes and nodes that identify a card, hidden unless `capture_redact_cards` is disabled.
+const CARD_KEYS: [&str; 3] = ["cardid", "refid", "dataid"];
+const REDACTED: &str = "[REDACTED]";
+
+/// Start of every capture name, the time it was made.
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+
+/// Tells apart captures made within the same millisecond.
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+/// Responses do not say which request they answer, this is the last one that was captured.
+static LAST_METHOD: RwLock<Option<String>> = RwLock::new(None);
+/// Captures are written by a thread of their own so the game never waits for the disk.
+static WRITER: Mutex<Option<Sender<Capture>>> = Mutex::new(None);
+
+/// A property waiting to be written, named when it was seen.
+struct Capture {
+    stem: String,
+    bytes: Vec<u8>,
+    redact_cards: bool,
+    limit: usize,
+}
+
+/// Captures a property seen by the hook, requests and responses alike.
+pub fn property(property: &impl PropertyApi) {
+    let name = match (property.call_name(), property.call_attribute("method")) {
+        (Some(name), Some(method)) => {
+            *LAST_METHOD.write().unwrap_or_else(|err| err.into_inner()) = Some(method.clone());
+            format!("{name}-{method}")
+        }
+        (Some(name), None) => name,
+        (None, _) => "property".to_string(),
+    };
+
+    let result = property.to_bytes().and_then(|bytes| queue(&name, bytes));
+    if let Err(err) = result {
+        error!("Could not capture {name} property: {err:#}");
+    }
+}
+
+/// Captures a response buffer read by the game, `stage` is `original` or `rewritten`.
+pub fn response(stage: &str, bytes: &[u8]) {
+    let method = LAST_METHOD
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+        .unwrap_or_else(|| "unknown".to_string());
+
+    if let Err(err) = queue(&format!("response-{method}-{stage}"), bytes.to_vec()) {
+        error!("Could not capture {method} response: {err:#}");
+    }
+}
+
+/// Hands a property over to the writer thread, starting it on first use.
+fn queue(name: &str, bytes: Vec<u8>) -> Result<()> {
+    let configuration = crate::configuration();
+    let capture = Capture {
+        stem: format!(
+            "{}-{:06}-{name}",
+            chrono::Local::now().format(TIME_FORMAT),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ),
+        bytes,
+        redact_cards: configuration.general.capture_redact_cards,
+        limit: configuration.general.capture_limit,
+    };
+
+    let mut writer = WRITER.lock().unwrap_or_else(|err| err.into_inner());
+    if writer.is_none() {
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("mikado-capture".to_string())
+            .spawn(move || run(rx))
+            .map_err(|err| anyhow::anyhow!("Could not start capture writer: {:#}", err))?;
+        *writer = Some(tx);
+    }
+
+    if let Some(sender) = writer.as_ref()
+        && sender.send(capture).is_err()
+    {
+        *writer = None;
+        return Err(anyhow::anyhow!("Capture writer has stopped"));
+    }
+
+    Ok(())
+}
+
+fn run(receiver: Receiver<Capture>) {
+    let mut stems = existing_stems();
+    for capture in receiver {
+        match write(&capture) {
+            Ok(()) => {
+                debug!("Captured {}", capture.stem);
+                stems.push_back(capture.stem);
+            }
+            Err(err) => error!("Could not capture {}: {err:#}", capture.stem),
+        }
+        rotate(&mut stems, capture.limit);
+    }
+}
+
+fn write(capture: &Capture) -> Result<()> {
+    let (collection, _) = kbinxml::from_bytes(Bytes::copy_from_slice(&capture.bytes))
+        .map_err(|err| anyhow::anyhow!("Could not parse property: {err}"))?;
+    let mut node = collection
+        .as_node()
+        .map_err(|err| anyhow::anyhow!("Could not parse property: {err}"))?;
+    if capture.redact_cards {
+        redact(&mut node);
+    }
+
+    std::fs::create_dir_all(CAPTURE_DIRECTORY)
+        .map_err(|err| anyhow::anyhow!("Could not create {CAPTURE_DIRECTORY} folder: {}", err))?;
+
+    let kbin = kbinxml::to_binary(&node)
+        .map_err(|err| anyhow::anyhow!("Could not write property as kbin: {err}"))?;
+    let xml = kbinxml::to_text_xml(&node)
+        .map_err(|err| anyhow::anyhow!("Could not write property as XML: {err}"))?;
+    for (extension, content) in [("kbin", kbin), ("xml", xml)] {
+        let path = Path::new(CAPTURE_DIRECTORY).join(format!("{}.{extension}", capture.stem));
+        std::fs::write(&path, content)
+            .map_err(|err| anyhow::anyhow!("Could not write {}: {}", path.display(), err))?;
+    }
+
+    Ok(())
+}
+
+/// Captures left by previous sessions, oldest first. The folder is only listed once, the writer
+/// keeps track of the captures it makes afterwards.
+fn existing_stems() -> VecDeque<String> {
+    let Ok(entries) = std::fs::read_dir(CAPTURE_DIRECTORY) else {
+        return VecDeque::new();
+    };
+    let mut stems = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension()? != "kbin" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect::<Vec<_>>();
+
+    // Names start with the time of the capture
+    stems.sort();
+    stems.into()
+}
+
+/// Removes the oldest captures so that at most `limit` of them are kept.
+fn rotate(stems: &mut VecDeque<String>, limit: usize) {
+    while stems.len() > limit {
+        let Some(stem) = stems.pop_front() else {
+            break;
+        };
+        for extension in ["kbin", "xml"] {
+            let path = Path::new(CAPTURE_DIRECTORY).join(format!("{stem}.{extension}"));
+            if let Err(err) = std::fs::remove_file(&path) {
+                debug!("Could not remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// When a capture was made, in milliseconds since the Unix epoch, read from its file name.
+pub fn time_from_name(file_name: &str) -> Option<u128> {
+    // 20240501-201500123, the date, the time and the milliseconds
+    let time = file_name.get(..18)?;
+    let time = chrono::NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+
+    time.and_local_timezone(chrono::Local)
+        .earliest()
+        .map(|time| time.timestamp_millis() as u128)
+}
+
+fn redact(node: &mut Node) {
+    for key in CARD_KEYS {
+        if node.attr(key).is_some() {
+            node.set_attr(key, REDACTED);
+        }
+    }
+    if CARD_KEYS.contains(&node.key())
+        && let Some(Value::String(value)) = node.value_mut()
+    {
+        *value = REDACTED.to_string();
+    }
+
+    for child in node.children_mut() {
+        redact(child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use chrono::TimeZone;
+
+    #[test]
+    fn reads_time_from_capture_name() {
+        let expected = chrono::Local
+            .with_ymd_and_hms(2024, 5, 1, 20, 15, 0)
+            .unwrap()
+            .timestamp_millis() as u128
+            + 123;
+
+        assert_eq!(
+            time_from_name("20240501-201500123-000042-game-sv6_save_m.kbin"),
+            Some(expected)
+        );
+        assert_eq!(time_from_name("save_m.kbin"), None);
+        assert_eq!(time_from_name("20241301-201500123-000042-game.kbin"), None);
+    }
+
+    #[test]
+    fn rotation_removes_oldest_captures() {
+        let _guard = testing::isolate();
+        std::fs::create_dir_all(CAPTURE_DIRECTORY).unwrap();
+        let names = [
+            "20240501-201500123-000001-game-sv6_common",
+            "20240501-201500123-000000-cardmng-inquire",
+            "20240502-090000000-000000-game-sv6_load",
+        ];
+        for name in names {
+            for extension in ["kbin", "xml"] {
+                let path = Path::new(CAPTURE_DIRECTORY).join(format!("{name}.{extension}"));
+                std::fs::write(path, "").unwrap();
+            }
+        }
+
+        let mut stems = existing_stems();
+        assert_eq!(stems, [names[1], names[0], names[2]]);
+
+        rotate(&mut stems, 1);
+        assert_eq!(stems, [names[2]]);
+        let mut left = std::fs::read_dir(CAPTURE_DIRECTORY)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(
+            left,
+            [format!("{}.kbin", names[2]), format!("{}.xml", names[2])]
+        );
+    }
+}
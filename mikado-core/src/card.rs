@@ -0,0 +1,167 @@
+/// Characters used by the IDs printed on e-amusement passes, I and O are left out as they look
+/// like 1 and 0.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKLMNPRSTUWXYZ";
+
+/// A card number as written in the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardId {
+    /// The ID sent by the game in `cardmng inquire`, like `E004010027A5FC68`.
+    Internal(String),
+    /// The ID printed on the card. It is enciphered from the internal one and Mikado can't
+    /// convert it back yet.
+    Printed(String),
+}
+
+/// Reads a card number, case, spaces and dashes are ignored.
+///
+/// Printed IDs are told apart by their checksum character. Internal IDs also pass it once in a
+/// while, those are read as internal IDs since printed ones can't be converted anyway.
+pub fn parse(card: &str) -> Option<CardId> {
+    let card = card
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    if card_type(&card).is_some() && card.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(CardId::Internal(card))
+    } else if decode_groups(&card).is_some() {
+        Some(CardId::Printed(card))
+    } else {
+        None
+    }
+}
+
+/// Returns the internal ID of a card as sent by the game, or `None` if `card` is not one.
+pub fn normalize(card: &str) -> Option<String> {
+    match parse(card)? {
+        CardId::Internal(internal_id) => Some(internal_id),
+        CardId::Printed(_) => None,
+    }
+}
+
+/// 1 for E004 (magnetic and e-amusement pass) cards, 2 for FeliCa ones.
+fn card_type(internal_id: &str) -> Option<u8> {
+    if internal_id.len() != 16 {
+        None
+    } else if internal_id.starts_with("E0") {
+        Some(1)
+    } else if internal_id.starts_with("01") {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn checksum(groups: &[u8; 16]) -> u8 {
+    let mut checksum = groups[..15]
+        .iter()
+        .enumerate()
+        .map(|(i, group)| (i as u32 % 3 + 1) * *group as u32)
+        .sum::<u32>();
+    while checksum >= 0x20 {
+        checksum = (checksum & 0x1F) + (checksum >> 5);
+    }
+
+    checksum as u8
+}
+
+/// Returns the enciphered ID a printed one is made of and the card type, checking its checksum.
+fn decode_groups(konami_id: &str) -> Option<([u8; 8], u8)> {
+    let konami_id = konami_id
+        .to_ascii_uppercase()
+        .replace('I', "1")
+        .replace('O', "0");
+    if konami_id.len() != 16 {
+        return None;
+    }
+
+    let mut groups = [0u8; 16];
+    for (group, c) in groups.iter_mut().zip(konami_id.bytes()) {
+        *group = ALPHABET.iter().position(|a| *a == c)? as u8;
+    }
+
+    let card_type = groups[14];
+    if !(1..=2).contains(&card_type) || groups[15] != checksum(&groups) {
+        return None;
+    }
+
+    for i in (1..14).rev() {
+        groups[i] ^= groups[i - 1];
+    }
+    groups[0] ^= card_type;
+
+    let mut block = [0u8; 8];
+    for i in 0..64 {
+        let bit = (groups[i / 5] >> (4 - i % 5)) & 1;
+        block[i / 8] |= bit << (7 - i % 8);
+    }
+
+    Some((block, card_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Card from the bemaniutils test suite, with the enciphered block its printed ID is made of.
+    const PRINTED_ID: &str = "S6E523E30ZK7ML1P";
+    const INTERNAL_ID: &str = "E004010027A5FC68";
+    const ENCIPHERED: [u8; 8] = [0xC7, 0xD0, 0xB3, 0x85, 0xAD, 0x1F, 0xD9, 0x49];
+
+    #[test]
+    fn decodes_known_printed_id() {
+        assert_eq!(decode_groups(PRINTED_ID), Some((ENCIPHERED, 1)));
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut printed = PRINTED_ID.to_string();
+        printed.replace_range(15..16, "Q");
+        assert_eq!(decode_groups(&printed), None);
+        assert_eq!(parse(&printed), None);
+    }
+
+    #[test]
+    fn reads_i_and_o_as_1_and_0() {
+        assert_eq!(decode_groups("S6E523E3OZK7MLIP"), Some((ENCIPHERED, 1)));
+        assert_eq!(decode_groups("s6e523e3ozk7mlip"), Some((ENCIPHERED, 1)));
+    }
+
+    #[test]
+    fn tells_printed_ids_from_internal_ones() {
+        assert_eq!(
+            parse(PRINTED_ID),
+            Some(CardId::Printed(PRINTED_ID.to_string()))
+        );
+        assert_eq!(normalize(PRINTED_ID), None);
+        assert_eq!(normalize(INTERNAL_ID).as_deref(), Some(INTERNAL_ID));
+        assert_eq!(
+            normalize("012E0100D9B61E3D").as_deref(),
+            Some("012E0100D9B61E3D")
+        );
+        // Valid checksum for a printed ID, but written like the ID sent by the game
+        assert_eq!(
+            decode_groups("E004010027000016").map(|(_, card_type)| card_type),
+            Some(1)
+        );
+        assert_eq!(
+            normalize("E004010027000016").as_deref(),
+            Some("E004010027000016")
+        );
+    }
+
+    #[test]
+    fn normalizes_dashes_spaces_and_case() {
+        assert_eq!(
+            normalize(" e004 0100-27a5 fc68 ").as_deref(),
+            Some(INTERNAL_ID)
+        );
+        assert_eq!(
+            parse("s6e5-23e3 0zk7-ml1p"),
+            Some(CardId::Printed(PRINTED_ID.to_string()))
+        );
+        assert_eq!(normalize("E004"), None);
+        assert_eq!(parse("E004"), None);
+    }
+}
@@ -0,0 +1,439 @@
+mod ext;
+mod rivals;
+
+pub use rivals::{prefetch_rivals, process_rivals};
+
+use crate::configuration::PbMerge;
+use crate::types::GameVersion;
+use crate::types::cloudlink::{Chart, Score};
+use crate::types::tachi::{TachiDifficulty, TachiLamp};
+use crate::types::user::{Target, User};
+use crate::{dry_run, helpers, hook};
+use anyhow::{Context, Result};
+use dynfmt::Format;
+use ext::HashMapExt;
+use kbinxml::{Node, Value, ValueArray};
+use log::{debug, error, info, warn};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+fn build_response_base(scores: Vec<Node>) -> Node {
+    Node::with_nodes(
+        "response",
+        vec![Node::with_nodes(
+            "game",
+            vec![Node::with_nodes("music", scores)],
+        )],
+    )
+}
+
+/// Writes a Tachi PB into the cloud fields of a chart the game already sent a score for.
+fn merge_pb(
+    strategy: PbMerge,
+    version: GameVersion,
+    base_score: &mut Score,
+    score: u32,
+    lamp: u32,
+    grade: u32,
+    ex_score: u32,
+) {
+    let keep_best = match strategy {
+        PbMerge::Tachi => false,
+        // Charts never played on cloud still get the Tachi PB
+        PbMerge::Cloud if *base_score.cloud_score_mut() != 0 => return,
+        PbMerge::Cloud => false,
+        PbMerge::Best => true,
+    };
+
+    let cloud_score = base_score.cloud_score_mut();
+    if !keep_best || score > *cloud_score {
+        *cloud_score = score;
+    }
+
+    // 0 means not played, which is below any lamp
+    let lamp_rank =
+        |index: u32| (index != 0).then(|| TachiLamp::from_clear_type(version, index).rank());
+    let cloud_clear = base_score.cloud_clear_mut();
+    if !keep_best || lamp_rank(lamp) > lamp_rank(*cloud_clear) {
+        *cloud_clear = lamp;
+    }
+
+    let cloud_grade = base_score.cloud_grade_mut();
+    if !keep_best || grade > *cloud_grade {
+        *cloud_grade = grade;
+    }
+
+    if let Some(cloud_ex_score) = base_score.cloud_ex_score_mut()
+        && (!keep_best || ex_score > *cloud_ex_score)
+    {
+        *cloud_ex_score = ex_score;
+    }
+}
+
+/// A Tachi PB, already converted to the game's values.
+pub(crate) struct Pb {
+    pub chart: Chart,
+    pub level: Option<f64>,
+    pub score: u32,
+    pub lamp: u32,
+    pub grade: u32,
+    pub ex_score: u32,
+}
+
+/// Fetches every PB of a Tachi user through the given target.
+pub(crate) fn fetch_pbs(target: &Target, tachi_id: u64) -> Result<Vec<Pb>> {
+    let url = dynfmt::SimpleCurlyFormat
+        .format(&target.urls.pbs, [tachi_id])
+        .map_err(|err| anyhow::anyhow!("Could not build Tachi PBs URL: {err}"))?;
+
+    let response: serde_json::Value = if dry_run::is_enabled() {
+        dry_run::pbs()?
+    } else {
+        helpers::request_tachi("GET", url, &target.api_key, None::<()>)
+            .context("Could not fetch PBs from Tachi")?
+    };
+    let body = response["body"]
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("Could not parse response body from Tachi PBs API"))?;
+    let pbs = body["pbs"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Could not parse PBs from Tachi PBs API"))?;
+    let charts = body["charts"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Could not parse charts from Tachi PBs API"))?;
+    let charts = charts
+        .iter()
+        .map(|chart| {
+            let chart_id = chart["chartID"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Could not parse chart ID from Tachi PBs API"))?;
+            let song_id = chart["data"]["inGameID"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Could not parse ingame ID from Tachi PBs API"))?
+                as u32;
+            let difficulty =
+                match serde_json::from_value::<TachiDifficulty>(chart["difficulty"].clone()) {
+                    Ok(difficulty) => u32::from(difficulty) as u8,
+                    Err(_) => 3,
+                };
+            let level = chart["levelNum"].as_f64();
+            Ok((
+                chart_id,
+                (
+                    Chart {
+                        song_id,
+                        difficulty,
+                    },
+                    level,
+                ),
+            ))
+        })
+        .collect::<Result<HashMap<&str, (Chart, Option<f64>)>>>()?;
+
+    let game_properties = hook::GAME_PROPERTIES.get();
+    let version = game_properties.map(|p| p.version()).unwrap_or_default();
+    let has_maxxive = game_properties
+        .map(|p| p.has_maxxive_support())
+        .unwrap_or_default();
+
+    pbs.iter()
+        .map(|pb| {
+            let chart_id = pb["chartID"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Could not parse chart ID from Tachi PBs API"))?;
+            let (chart, level) = charts
+                .get(chart_id)
+                .ok_or_else(|| anyhow::anyhow!("Could not find chart"))?;
+            let score = pb["scoreData"]["score"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Could not parse PB score from Tachi PBs API"))?;
+
+            let lamp = match serde_json::from_value::<TachiLamp>(pb["scoreData"]["lamp"].clone()) {
+                Ok(TachiLamp::MaxxiveClear) if !has_maxxive => TachiLamp::ExcessiveClear,
+                Ok(lamp) => lamp,
+                Err(_) => TachiLamp::Failed,
+            }
+            .to_index(version);
+
+            let grade = pb["scoreData"]["enumIndexes"]["grade"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Could not parse PB grade from Tachi PBs API"))?
+                + 1;
+            let grade = if grade >= 11 { 10 } else { grade };
+
+            let ex_score = pb["scoreData"]["optional"]["exScore"].as_u64().unwrap_or(0) as u32;
+
+            Ok(Pb {
+                chart: *chart,
+                level: *level,
+                score: score as u32,
+                lamp,
+                grade: grade as u32,
+                ex_score,
+            })
+        })
+        .collect()
+}
+
+/// Keeps the PBs to tell new ones apart and compute the Volforce.
+fn seed(user: &User, pbs: &[Pb]) {
+    let version = hook::GAME_PROPERTIES
+        .get()
+        .map(|p| p.version())
+        .unwrap_or_default();
+    crate::pbs::seed(&user.card_id, version, pbs);
+    crate::volforce::seed(&user.card_id, version, pbs);
+}
+
+/// Loads the PBs of the user in the background, for when they are not injected in game.
+pub fn preload_pbs(user: &User) {
+    let user = user.clone();
+    let spawned = std::thread::Builder::new()
+        .name("mikado-pbs".to_string())
+        .spawn(
+            move || match fetch_pbs(user.profile.primary(), user.tachi_id) {
+                Ok(pbs) => {
+                    debug!("Loaded {} Tachi PB(s)", pbs.len());
+                    seed(&user, &pbs);
+                }
+                Err(err) => {
+                    warn!("Could not load Tachi PBs, new PBs will not be announced: {err:#}")
+                }
+            },
+        );
+    if let Err(err) = spawned {
+        error!("Could not start loading Tachi PBs: {err}");
+    }
+}
+
+pub fn process_pbs(user: &User, music: &Node) -> Result<Node> {
+    let pbs = fetch_pbs(user.profile.primary(), user.tachi_id)?;
+
+    seed(user, &pbs);
+
+    let version = hook::GAME_PROPERTIES
+        .get()
+        .map(|p| p.version())
+        .unwrap_or_default();
+    let pb_merge = crate::configuration().general.pb_merge;
+
+    let mut scores = HashMap::with_capacity(music.children().len() + pbs.len());
+    for pb in music.children() {
+        let score = pb
+            .children()
+            .first()
+            .ok_or_else(|| anyhow::anyhow!("Could not find param node"))?;
+        if let Value::Array(ValueArray::U32(value)) = score
+            .value()
+            .ok_or_else(|| anyhow::anyhow!("Could not find value in param node"))?
+        {
+            let song_id = value[0];
+            let difficulty = value[1] as u8;
+            let chart = Chart {
+                song_id,
+                difficulty,
+            };
+            let score = Score::from_slice(version, value)?;
+            scores.insert(chart, score);
+        }
+    }
+
+    for pb in pbs {
+        match scores.entry(pb.chart) {
+            Entry::Occupied(mut entry) => merge_pb(
+                pb_merge,
+                version,
+                entry.get_mut(),
+                pb.score,
+                pb.lamp,
+                pb.grade,
+                pb.ex_score,
+            ),
+            Entry::Vacant(entry) => {
+                let score = Score::from_cloud(
+                    version,
+                    pb.score,
+                    pb.lamp as u8,
+                    pb.grade as u8,
+                    pb.ex_score,
+                );
+                entry.insert(score);
+            }
+        }
+    }
+
+    let response = build_response_base(scores.to_properties());
+    info!("Successfully injected Tachi PBs as Cloud scores");
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Score, clear, grade and EX score as the game reads them.
+    fn fields(mut score: Score) -> (u32, u32, u32, Option<u32>) {
+        (
+            *score.cloud_score_mut(),
+            *score.cloud_clear_mut(),
+            *score.cloud_grade_mut(),
+            score.cloud_ex_score_mut().map(|ex_score| *ex_score),
+        )
+    }
+
+    fn exceed_gear(score: u32, clear: u8, grade: u8) -> Score {
+        Score::from_cloud(GameVersion::ExceedGear, score, clear, grade, 0)
+    }
+
+    fn nabla(score: u32, clear: u8, grade: u8, ex_score: u32) -> Score {
+        Score::from_cloud(GameVersion::Nabla, score, clear, grade, ex_score)
+    }
+
+    #[test]
+    fn tachi_replaces_cloud_score() {
+        let mut score = exceed_gear(9_900_000, 4, 10);
+        merge_pb(
+            PbMerge::Tachi,
+            GameVersion::ExceedGear,
+            &mut score,
+            9_000_000,
+            2,
+            7,
+            0,
+        );
+        assert_eq!(fields(score), (9_000_000, 2, 7, None));
+
+        let mut score = nabla(9_900_000, 5, 10, 2000);
+        merge_pb(
+            PbMerge::Tachi,
+            GameVersion::Nabla,
+            &mut score,
+            9_000_000,
+            4,
+            7,
+            1500,
+        );
+        assert_eq!(fields(score), (9_000_000, 4, 7, Some(1500)));
+    }
+
+    #[test]
+    fn cloud_keeps_played_charts() {
+        let mut score = nabla(9_000_000, 2, 7, 1500);
+        merge_pb(
+            PbMerge::Cloud,
+            GameVersion::Nabla,
+            &mut score,
+            9_900_000,
+            6,
+            10,
+            2000,
+        );
+        assert_eq!(fields(score), (9_000_000, 2, 7, Some(1500)));
+    }
+
+    #[test]
+    fn cloud_fills_charts_with_a_score_of_0() {
+        let mut score = nabla(0, 0, 0, 0);
+        merge_pb(
+            PbMerge::Cloud,
+            GameVersion::Nabla,
+            &mut score,
+            9_500_000,
+            3,
+            8,
+            1800,
+        );
+        assert_eq!(fields(score), (9_500_000, 3, 8, Some(1800)));
+
+        let mut score = exceed_gear(0, 0, 0);
+        merge_pb(
+            PbMerge::Cloud,
+            GameVersion::ExceedGear,
+            &mut score,
+            9_500_000,
+            3,
+            8,
+            0,
+        );
+        assert_eq!(fields(score), (9_500_000, 3, 8, None));
+    }
+
+    #[test]
+    fn best_ranks_nabla_maxxive_below_ultimate_chain() {
+        // MAXXIVE CLEAR is index 4 and ULTIMATE CHAIN index 5 on Nabla
+        let mut score = nabla(9_800_000, 5, 9, 1700);
+        merge_pb(
+            PbMerge::Best,
+            GameVersion::Nabla,
+            &mut score,
+            9_850_000,
+            4,
+            9,
+            1650,
+        );
+        assert_eq!(fields(score), (9_850_000, 5, 9, Some(1700)));
+
+        let mut score = nabla(9_850_000, 4, 9, 1650);
+        merge_pb(
+            PbMerge::Best,
+            GameVersion::Nabla,
+            &mut score,
+            9_800_000,
+            5,
+            9,
+            1700,
+        );
+        assert_eq!(fields(score), (9_850_000, 5, 9, Some(1700)));
+    }
+
+    #[test]
+    fn best_ranks_exceed_gear_maxxive_below_ultimate_chain() {
+        // MAXXIVE CLEAR is index 6 and ULTIMATE CHAIN index 4 on Exceed Gear
+        let mut score = exceed_gear(9_850_000, 6, 9);
+        merge_pb(
+            PbMerge::Best,
+            GameVersion::ExceedGear,
+            &mut score,
+            9_800_000,
+            4,
+            9,
+            0,
+        );
+        assert_eq!(fields(score), (9_850_000, 4, 9, None));
+    }
+
+    #[test]
+    fn best_takes_a_failed_lamp_over_an_unplayed_one() {
+        let mut score = nabla(0, 0, 0, 0);
+        merge_pb(
+            PbMerge::Best,
+            GameVersion::Nabla,
+            &mut score,
+            5_000_000,
+            1,
+            1,
+            500,
+        );
+        assert_eq!(fields(score), (5_000_000, 1, 1, Some(500)));
+    }
+
+    #[test]
+    fn ex_score_only_exists_on_nabla() {
+        let mut score = exceed_gear(9_000_000, 2, 7);
+        merge_pb(
+            PbMerge::Best,
+            GameVersion::ExceedGear,
+            &mut score,
+            8_000_000,
+            2,
+            6,
+            u32::MAX,
+        );
+        assert_eq!(fields(score), (9_000_000, 2, 7, None));
+        assert_eq!(
+            score.to_property(),
+            exceed_gear(9_000_000, 2, 7).to_property()
+        );
+    }
+}
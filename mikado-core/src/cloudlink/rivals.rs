@@ -0,0 +1,276 @@
+use super::{Pb, fetch_pbs};
+use crate::types::GameVersion;
+use crate::types::tachi::{TachiResponse, TachiUser};
+use crate::types::user::User;
+use crate::{helpers, hook};
+use anyhow::{Context, Result};
+use dynfmt::Format;
+use kbinxml::{Node, Value, ValueArray};
+use log::{debug, error, info, warn};
+use std::sync::Mutex;
+
+fn rival_music(version: GameVersion, pb: &Pb) -> Node {
+    let param = match version {
+        GameVersion::ExceedGear => vec![
+            pb.chart.song_id,
+            pb.chart.difficulty as u32,
+            pb.score,
+            pb.lamp,
+            pb.grade,
+        ],
+        GameVersion::Nabla => vec![
+            pb.chart.song_id,
+            pb.chart.difficulty as u32,
+            pb.score,
+            pb.ex_score,
+            pb.lamp,
+            pb.grade,
+        ],
+    };
+
+    Node::with_value("music", Value::Array(ValueArray::U32(param)))
+}
+
+/// Rivals of the card being played, fetched in the background when the card is scanned.
+static RIVALS: Mutex<Option<CachedRivals>> = Mutex::new(None);
+
+struct CachedRivals {
+    card_id: String,
+    rivals: Vec<Rival>,
+}
+
+struct Rival {
+    id: u64,
+    username: String,
+    pbs: Vec<Pb>,
+}
+
+/// Fetches the user's Tachi rivals and their PBs in the background, so that the `load_r`
+/// response never waits for Tachi.
+pub fn prefetch_rivals(user: &User) {
+    // The rivals of the previous player must not be shown while these load
+    clear_rivals();
+
+    let user = user.clone();
+    let spawned = std::thread::Builder::new()
+        .name("mikado-rivals".to_string())
+        .spawn(move || match fetch_rivals(&user) {
+            Ok(rivals) => {
+                debug!("Loaded {} Tachi rival(s)", rivals.len());
+                match RIVALS.lock() {
+                    Ok(mut cached) => {
+                        *cached = Some(CachedRivals {
+                            card_id: user.card_id.clone(),
+                            rivals,
+                        })
+                    }
+                    Err(err) => error!("Rivals Mutex is poisoned: {err:#}"),
+                }
+            }
+            Err(err) => warn!("Could not load Tachi rivals, the game's will be kept: {err:#}"),
+        });
+    if let Err(err) = spawned {
+        error!("Could not start loading Tachi rivals: {err}");
+    }
+}
+
+fn clear_rivals() {
+    match RIVALS.lock() {
+        Ok(mut cached) => *cached = None,
+        Err(err) => error!("Rivals Mutex is poisoned: {err:#}"),
+    }
+}
+
+fn fetch_rivals(user: &User) -> Result<Vec<Rival>> {
+    let max_rivals = crate::configuration().general.max_rivals;
+
+    let target = user.profile.primary();
+    let url = dynfmt::SimpleCurlyFormat
+        .format(&target.urls.rivals, [user.tachi_id])
+        .map_err(|err| anyhow::anyhow!("Could not build Tachi rivals URL: {err}"))?;
+    let rivals = helpers::request_tachi::<(), TachiResponse<Vec<TachiUser>>>(
+        "GET",
+        url,
+        &target.api_key,
+        None,
+    )
+    .and_then(TachiResponse::into_body)
+    .context("Could not fetch rivals from Tachi")?;
+
+    let mut fetched = Vec::with_capacity(rivals.len().min(max_rivals));
+    for rival in rivals.into_iter().take(max_rivals) {
+        match fetch_pbs(target, rival.id) {
+            Ok(pbs) => fetched.push(Rival {
+                id: rival.id,
+                username: rival.username,
+                pbs,
+            }),
+            Err(err) => warn!("Skipping rival {}: {err:#}", rival.username),
+        }
+    }
+
+    Ok(fetched)
+}
+
+/// Replaces the response to `load_r` with the user's Tachi rivals and their PBs.
+///
+/// Returns `None` if they are not loaded yet, the game's rivals are then kept.
+pub fn process_rivals(user: &User) -> Option<Node> {
+    let version = hook::GAME_PROPERTIES
+        .get()
+        .map(|p| p.version())
+        .unwrap_or_default();
+
+    let cached = RIVALS.lock().unwrap_or_else(|err| {
+        error!("Rivals Mutex is poisoned: {err:#}");
+        err.into_inner()
+    });
+    let Some(cached) = cached
+        .as_ref()
+        .filter(|cached| cached.card_id == user.card_id)
+    else {
+        warn!("Tachi rivals are not loaded yet, keeping the game's rivals");
+        return None;
+    };
+
+    let nodes = cached
+        .rivals
+        .iter()
+        .enumerate()
+        .map(|(index, rival)| {
+            let mut children = vec![
+                Node::with_value("no", Value::S16(index as i16)),
+                Node::with_value("seq", Value::U8(1)),
+                // The game expects an SDVX ID, the Tachi one is only used to tell rivals apart
+                Node::with_value(
+                    "userid",
+                    Value::String(format!(
+                        "{:04}-{:04}",
+                        rival.id / 10000 % 10000,
+                        rival.id % 10000
+                    )),
+                ),
+                Node::with_value("name", Value::String(rival.username.clone())),
+            ];
+            children.extend(rival.pbs.iter().map(|pb| rival_music(version, pb)));
+            Node::with_nodes("rival", children)
+        })
+        .collect::<Vec<_>>();
+
+    info!("Successfully injected {} Tachi rival(s)", nodes.len());
+
+    Some(Node::with_nodes(
+        "response",
+        vec![Node::with_nodes("game", nodes)],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::GeneralConfiguration;
+    use crate::testing::{self, MockTachi};
+    use std::time::{Duration, Instant};
+
+    const RIVAL_ID: u64 = 10_001_234;
+    const RIVALS_DELAY: Duration = Duration::from_millis(500);
+
+    fn mock_tachi() -> MockTachi {
+        MockTachi::start(|request| match request.path.as_str() {
+            "/rivals/42" => (
+                RIVALS_DELAY,
+                testing::tachi_response(
+                    serde_json::json!([{ "id": RIVAL_ID, "username": "RIVAL" }]),
+                ),
+            ),
+            _ => (
+                Duration::ZERO,
+                testing::tachi_response(serde_json::json!({
+                    "pbs": [{
+                        "chartID": "chart",
+                        "scoreData": {
+                            "score": 9_876_543,
+                            "lamp": "EXCESSIVE CLEAR",
+                            "enumIndexes": { "grade": 8 },
+                            "optional": { "exScore": 1234 },
+                        },
+                    }],
+                    "charts": [{
+                        "chartID": "chart",
+                        "data": { "inGameID": 1500 },
+                        "difficulty": "EXH",
+                        "levelNum": 17.0,
+                    }],
+                })),
+            ),
+        })
+    }
+
+    fn user() -> User {
+        User {
+            tachi_id: 42,
+            card_id: testing::CARD_ID.to_string(),
+            profile: helpers::get_profile(testing::CARD_ID).unwrap(),
+            session_started: chrono::Local::now(),
+        }
+    }
+
+    #[test]
+    fn serves_rivals_loaded_in_background() {
+        let _guard = testing::isolate();
+        let tachi = mock_tachi();
+        testing::configure(
+            &tachi.base_url,
+            GeneralConfiguration {
+                inject_rivals: true,
+                max_rivals: 4,
+                ..Default::default()
+            },
+        );
+        let user = user();
+
+        let started = Instant::now();
+        prefetch_rivals(&user);
+        assert!(process_rivals(&user).is_none());
+        assert!(started.elapsed() < RIVALS_DELAY);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let response = loop {
+            if let Some(response) = process_rivals(&user) {
+                break response;
+            }
+            assert!(Instant::now() < deadline, "rivals were never loaded");
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        let game = &response.children()[0];
+        assert_eq!((response.key(), game.key()), ("response", "game"));
+        let rival = &game.children()[0];
+        assert_eq!(rival.key(), "rival");
+        let fields = rival
+            .children()
+            .iter()
+            .map(|node| (node.key(), node.value().cloned()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            [
+                ("no", Some(Value::S16(0))),
+                ("seq", Some(Value::U8(1))),
+                ("userid", Some(Value::String("1000-1234".to_string()))),
+                ("name", Some(Value::String("RIVAL".to_string()))),
+                // Exceed Gear layout: song, difficulty, score, lamp, grade
+                (
+                    "music",
+                    Some(Value::Array(ValueArray::U32(vec![
+                        1500, 2, 9_876_543, 3, 9
+                    ])))
+                ),
+            ]
+        );
+
+        let mut other = user.clone();
+        other.card_id = "E004000000000000".to_string();
+        assert!(process_rivals(&other).is_none());
+    }
+}
@@ -0,0 +1,621 @@
+use crate::card;
+use crate::types::user::{DEFAULT_TARGET, Profile, TachiUrls, Target};
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::Path;
+
+pub const CONFIGURATION_PATH: &str = "mikado.toml";
+
+/// API keys shipped in the default configuration file.
+const PLACEHOLDER_API_KEYS: [&str; 2] = ["your-key-here", "another-key-here"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Configuration {
+    pub general: GeneralConfiguration,
+    #[serde(default)]
+    pub cards: Option<CardsConfiguration>,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfiguration>,
+    pub tachi: TachiConfiguration,
+}
+
+impl Configuration {
+    pub fn load() -> Result<Self> {
+        if !Path::new(CONFIGURATION_PATH).exists() {
+            File::create(CONFIGURATION_PATH)
+                .and_then(|mut file| file.write_all(include_bytes!("../mikado.toml")))
+                .map_err(|err| anyhow::anyhow!("Could not create default config file: {}", err))?;
+        }
+
+        Self::load_path(CONFIGURATION_PATH)
+    }
+
+    /// Unlike [`Configuration::load`], a missing file is an error.
+    pub fn load_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(anyhow::anyhow!("Could not find {}", path.display()));
+        }
+
+        confy::load_path(path).map_err(|err| anyhow::anyhow!("Could not load config: {}", err))
+    }
+
+    /// Replaces `env:NAME` and `file:path` API keys with the key they point to, and makes sure
+    /// none of the keys ever ends up in the logs.
+    pub fn resolve_api_keys(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut resolve = |api_key: &mut String, location: &str| {
+            match resolve_secret(api_key) {
+                Ok(resolved) => *api_key = resolved,
+                Err(err) => {
+                    diagnostics.push(Diagnostic::error(format!("API key {location}: {err:#}")));
+                    return;
+                }
+            }
+            // Placeholders are not secret and redacting them would hide the diagnostic about them
+            if !PLACEHOLDER_API_KEYS.contains(&api_key.as_str()) {
+                crate::redact::redact(api_key);
+            }
+        };
+
+        if let Some(api_key) = &mut self.tachi.api_key {
+            resolve(api_key, "in [tachi]");
+        }
+        for (profile_name, profile) in &mut self.profiles {
+            if let Some(api_key) = &mut profile.api_key {
+                resolve(api_key, &format!("of profile \"{profile_name}\""));
+            }
+            for target in &mut profile.targets {
+                resolve(
+                    &mut target.api_key,
+                    &format!(
+                        "of target \"{}\" in profile \"{profile_name}\"",
+                        target.name
+                    ),
+                );
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Reports every problem at once instead of stopping at the first one.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let live = self.general.submission == SubmissionMode::Live && !self.general.dry_run;
+
+        // Keys are never used when scores are only written to files
+        let check_api_key = |api_key: &str, location: &str| {
+            if !live {
+                None
+            } else if api_key.trim().is_empty() {
+                Some(Diagnostic::error(format!("API key {location} is empty")))
+            } else if PLACEHOLDER_API_KEYS.contains(&api_key) {
+                Some(Diagnostic::error(format!(
+                    "API key {location} is still '{api_key}', replace it with a key generated on your Tachi account"
+                )))
+            } else {
+                None
+            }
+        };
+
+        // The [tachi] key is only used by cards of the [cards] whitelist
+        if self.cards.is_some() {
+            match &self.tachi.api_key {
+                Some(api_key) => diagnostics.extend(check_api_key(api_key, "in [tachi]")),
+                None if live => diagnostics.push(Diagnostic::error(
+                    "[cards] is set but [tachi] has no api_key to submit their scores with",
+                )),
+                None => {}
+            }
+        }
+        for (profile_name, profile) in &self.profiles {
+            if let Some(api_key) = &profile.api_key {
+                diagnostics.extend(check_api_key(
+                    api_key,
+                    &format!("of profile \"{profile_name}\""),
+                ));
+            }
+            for target in &profile.targets {
+                diagnostics.extend(check_api_key(
+                    &target.api_key,
+                    &format!(
+                        "of target \"{}\" in profile \"{profile_name}\"",
+                        target.name
+                    ),
+                ));
+            }
+        }
+
+        let check_urls = |urls: Result<TachiUrls>, location: &str| match urls {
+            Ok(urls) if !urls.pbs.contains("{}") => Some(Diagnostic::error(format!(
+                "PBs endpoint {location} has no {{}} where the Tachi user ID goes: {}",
+                urls.pbs
+            ))),
+            Ok(urls) if !urls.rivals.contains("{}") => Some(Diagnostic::error(format!(
+                "Rivals endpoint {location} has no {{}} where the Tachi user ID goes: {}",
+                urls.rivals
+            ))),
+            Ok(_) => None,
+            Err(err) => Some(Diagnostic::error(format!("{err:#} {location}"))),
+        };
+
+        diagnostics.extend(check_urls(self.tachi.tachi_urls(), "in [tachi]"));
+        for (profile_name, profile) in &self.profiles {
+            if profile.api_key.is_some() {
+                diagnostics.extend(check_urls(
+                    self.tachi.tachi_urls_with(
+                        profile.base_url.as_deref(),
+                        profile.status.as_deref(),
+                        profile.import.as_deref(),
+                        profile.pbs.as_deref(),
+                    ),
+                    &format!("in profile \"{profile_name}\""),
+                ));
+            }
+            for target in &profile.targets {
+                diagnostics.extend(check_urls(
+                    self.tachi.tachi_urls_with(
+                        target.base_url.as_deref(),
+                        target.status.as_deref(),
+                        target.import.as_deref(),
+                        target.pbs.as_deref(),
+                    ),
+                    &format!(
+                        "in target \"{}\" of profile \"{profile_name}\"",
+                        target.name
+                    ),
+                ));
+            }
+        }
+
+        if let Some(cards) = &self.cards {
+            for card in &cards.whitelist {
+                if card::normalize(card).is_none() {
+                    diagnostics.push(card_diagnostic(card, "the [cards] whitelist"));
+                }
+            }
+        }
+        let whitelist = self.whitelist();
+
+        let mut names: Vec<_> = self.profiles.keys().collect();
+        names.sort();
+        let mut seen_cards: HashMap<String, &str> = HashMap::new();
+        for profile_name in names {
+            let profile = &self.profiles[profile_name];
+            if profile.cards.is_empty() {
+                diagnostics.push(Diagnostic::warning(format!(
+                    "Profile \"{profile_name}\" has no cards, it will never be used"
+                )));
+            }
+            if profile.api_key.is_none() && profile.targets.is_empty() {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Profile \"{profile_name}\" has no api_key and no targets"
+                )));
+            }
+            let mut target_names = vec![];
+            for target in &profile.targets {
+                if (target.name == DEFAULT_TARGET && profile.api_key.is_some())
+                    || target_names.contains(&target.name.as_str())
+                {
+                    diagnostics.push(Diagnostic::error(format!(
+                        "Profile \"{profile_name}\" has more than one target named \"{}\"",
+                        target.name
+                    )));
+                }
+                target_names.push(&target.name);
+            }
+            if let Some(primary) = &profile.primary
+                && !(primary == DEFAULT_TARGET && profile.api_key.is_some())
+                && !target_names.contains(&primary.as_str())
+            {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Profile \"{profile_name}\" has no target named \"{primary}\" to use as primary"
+                )));
+            }
+
+            for card in &profile.cards {
+                let Some(internal_id) = card::normalize(card) else {
+                    diagnostics.push(card_diagnostic(
+                        card,
+                        &format!("profile \"{profile_name}\""),
+                    ));
+                    continue;
+                };
+                if whitelist.contains(&internal_id) {
+                    diagnostics.push(Diagnostic::warning(format!(
+                        "Card {card} is in the default [cards] whitelist and also assigned to profile \"{profile_name}\". The profile assignment will be ignored. Remove it from the [cards] whitelist if you want it to use the profile."
+                    )));
+                } else if let Some(existing_profile) = seen_cards.get(&internal_id) {
+                    diagnostics.push(Diagnostic::warning(format!(
+                        "Card {card} is assigned to both profile \"{existing_profile}\" and profile \"{profile_name}\", only \"{existing_profile}\" will be used"
+                    )));
+                } else {
+                    seen_cards.insert(internal_id, profile_name);
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Internal IDs of the `[cards]` whitelist, whatever form they were written in.
+    fn whitelist(&self) -> Vec<String> {
+        self.cards
+            .iter()
+            .flat_map(|cards| &cards.whitelist)
+            .filter_map(|card| card::normalize(card))
+            .collect()
+    }
+}
+
+fn resolve_secret(value: &str) -> Result<String> {
+    if let Some(name) = value.strip_prefix("env:") {
+        std::env::var(name)
+            .map_err(|err| anyhow::anyhow!("Could not read environment variable {name}: {err}"))
+    } else if let Some(path) = value.strip_prefix("file:") {
+        std::fs::read_to_string(path)
+            .map(|content| content.trim().to_string())
+            .map_err(|err| anyhow::anyhow!("Could not read {path}: {err}"))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Warning for a card that can't be matched against the ID sent by the game.
+fn card_diagnostic(card: &str, location: &str) -> Diagnostic {
+    if let Some(card::CardId::Printed(_)) = card::parse(card) {
+        Diagnostic::warning(format!(
+            "Card \"{card}\" in {location} is the ID printed on the card, which Mikado can't read yet. Scan the card and use the ID shown in the console instead"
+        ))
+    } else {
+        Diagnostic::warning(format!(
+            "Card \"{card}\" in {location} is not a valid card number, expected the 16 character ID sent by the game, like E004010027A5FC68"
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The configuration can't be used.
+    Error,
+    /// The configuration works but probably not the way it was meant to.
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+/// The configuration along with everything derived from it, swapped as a whole on reload so that
+/// readers never see profiles built from another version of the file.
+#[derive(Debug)]
+pub struct ActiveConfiguration {
+    configuration: Configuration,
+    /// Keyed by internal card ID.
+    pub card_profiles: HashMap<String, Profile>,
+    /// Internal card IDs.
+    pub whitelist: Vec<String>,
+    pub default_tachi_urls: TachiUrls,
+}
+
+impl ActiveConfiguration {
+    /// Warnings are logged, errors are all returned together.
+    pub fn new(mut configuration: Configuration) -> Result<Self> {
+        let mut diagnostics = configuration.resolve_api_keys();
+        diagnostics.extend(configuration.validate());
+        let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics
+            .into_iter()
+            .partition(|diagnostic| diagnostic.severity == Severity::Error);
+        for warning in &warnings {
+            warn!("{warning}");
+        }
+        if !errors.is_empty() {
+            let errors = errors
+                .iter()
+                .map(|error| format!("\n- {error}"))
+                .collect::<String>();
+            return Err(anyhow::anyhow!(
+                "Found the following problem(s) in {CONFIGURATION_PATH}:{errors}"
+            ));
+        }
+
+        let whitelist = configuration.whitelist();
+        let mut names: Vec<_> = configuration.profiles.keys().collect();
+        names.sort();
+        let mut card_profiles: HashMap<String, Profile> = HashMap::new();
+        for profile_name in names {
+            let profile_config = &configuration.profiles[profile_name];
+            let profile = profile_config
+                .profile(profile_name, &configuration.tachi)
+                .map_err(|err| err.context(format!("in profile \"{profile_name}\"")))?;
+
+            // Conflicts were reported by the validation
+            for card in profile_config
+                .cards
+                .iter()
+                .filter_map(|card| card::normalize(card))
+            {
+                if whitelist.contains(&card) || card_profiles.contains_key(&card) {
+                    continue;
+                }
+                card_profiles.insert(card, profile.clone());
+            }
+        }
+
+        let default_tachi_urls = configuration.tachi.tachi_urls()?;
+
+        Ok(Self {
+            configuration,
+            card_profiles,
+            whitelist,
+            default_tachi_urls,
+        })
+    }
+
+    /// Used when the configuration can't be loaded, Mikado then stays out of the way.
+    pub fn disabled() -> Self {
+        Self {
+            configuration: Configuration::default(),
+            card_profiles: HashMap::new(),
+            whitelist: vec![],
+            default_tachi_urls: TachiUrls::default(),
+        }
+    }
+}
+
+impl Deref for ActiveConfiguration {
+    type Target = Configuration;
+
+    fn deref(&self) -> &Self::Target {
+        &self.configuration
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GeneralConfiguration {
+    #[serde(default = "default_true")]
+    pub enable: bool,
+    #[serde(default)]
+    pub export_class: bool,
+    #[serde(default)]
+    pub inject_cloud_pbs: bool,
+    /// How injected Tachi PBs are combined with the cloud scores the game already has.
+    #[serde(default)]
+    pub pb_merge: PbMerge,
+    /// Whether the game's rivals should be replaced by Tachi rivals.
+    #[serde(default)]
+    pub inject_rivals: bool,
+    #[serde(default = "default_max_rivals")]
+    pub max_rivals: usize,
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default)]
+    pub submission: SubmissionMode,
+    /// Formats the score history is exported to when the game exits.
+    #[serde(default)]
+    pub history_export: Vec<HistoryExport>,
+    /// Whether the e-amusement traffic is written to `mikado-capture` for bug reports.
+    #[serde(default)]
+    pub capture: bool,
+    /// How many captures are kept, the oldest ones are removed first.
+    #[serde(default = "default_capture_limit")]
+    pub capture_limit: usize,
+    #[serde(default = "default_true")]
+    pub capture_redact_cards: bool,
+    /// Whether imports are written to `mikado-dry-run` instead of being sent, Tachi is never contacted.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Tachi PBs response injected in dry-run mode.
+    #[serde(default = "default_dry_run_pbs")]
+    pub dry_run_pbs: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubmissionMode {
+    /// Scores are sent to Tachi as soon as they are played.
+    #[default]
+    Live,
+    /// Scores are written to BATCH-MANUAL files to be uploaded by hand, Tachi is never contacted.
+    BatchManual,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PbMerge {
+    /// Tachi PBs replace cloud scores.
+    #[default]
+    Tachi,
+    /// Tachi PBs only fill charts without a cloud score.
+    Cloud,
+    /// Score, lamp, grade and EX score each keep the best of both.
+    Best,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistoryExport {
+    Csv,
+    BatchManual,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    3000
+}
+
+fn default_max_rivals() -> usize {
+    4
+}
+
+fn default_capture_limit() -> usize {
+    500
+}
+
+fn default_dry_run_pbs() -> String {
+    "mikado.dry-run.pbs.json".to_string()
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CardsConfiguration {
+    #[serde(default)]
+    pub whitelist: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileConfiguration {
+    pub cards: Vec<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub import: Option<String>,
+    #[serde(default)]
+    pub pbs: Option<String>,
+    #[serde(default)]
+    pub targets: Vec<TargetConfiguration>,
+    /// Name of the target PBs are read from, defaults to the first one.
+    #[serde(default)]
+    pub primary: Option<String>,
+}
+
+impl ProfileConfiguration {
+    /// The profile's own `api_key` makes up a target named "default", listed before the
+    /// `[[profiles.X.targets]]` ones.
+    pub fn profile(&self, name: &str, tachi: &TachiConfiguration) -> Result<Profile> {
+        let mut targets = Vec::with_capacity(self.targets.len() + 1);
+        if let Some(api_key) = &self.api_key {
+            targets.push(Target {
+                name: DEFAULT_TARGET.to_string(),
+                api_key: api_key.clone(),
+                urls: tachi.tachi_urls_with(
+                    self.base_url.as_deref(),
+                    self.status.as_deref(),
+                    self.import.as_deref(),
+                    self.pbs.as_deref(),
+                )?,
+            });
+        }
+
+        for target in &self.targets {
+            if targets.iter().any(|existing| existing.name == target.name) {
+                return Err(anyhow::anyhow!(
+                    "Profile \"{name}\" has more than one target named \"{}\"",
+                    target.name
+                ));
+            }
+
+            targets.push(Target {
+                name: target.name.clone(),
+                api_key: target.api_key.clone(),
+                urls: tachi
+                    .tachi_urls_with(
+                        target.base_url.as_deref(),
+                        target.status.as_deref(),
+                        target.import.as_deref(),
+                        target.pbs.as_deref(),
+                    )
+                    .map_err(|err| err.context(format!("in target \"{}\"", target.name)))?,
+            });
+        }
+
+        Profile::new(name.to_string(), targets, self.primary.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetConfiguration {
+    pub name: String,
+    pub api_key: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub import: Option<String>,
+    #[serde(default)]
+    pub pbs: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TachiConfiguration {
+    pub base_url: String,
+    pub status: String,
+    pub import: String,
+    pub pbs: String,
+    #[serde(default = "default_rivals")]
+    pub rivals: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+fn default_rivals() -> String {
+    "/api/v1/users/{}/games/sdvx/Single/rivals".to_string()
+}
+
+impl TachiConfiguration {
+    pub fn tachi_urls(&self) -> Result<TachiUrls> {
+        TachiUrls::new(
+            &self.base_url,
+            &self.status,
+            &self.import,
+            &self.pbs,
+            &self.rivals,
+        )
+    }
+
+    /// Anything that isn't overridden is taken from the `[tachi]` section, the rivals endpoint
+    /// always is.
+    pub fn tachi_urls_with(
+        &self,
+        base_url: Option<&str>,
+        status: Option<&str>,
+        import: Option<&str>,
+        pbs: Option<&str>,
+    ) -> Result<TachiUrls> {
+        TachiUrls::new(
+            base_url.unwrap_or(&self.base_url),
+            status.unwrap_or(&self.status),
+            import.unwrap_or(&self.import),
+            pbs.unwrap_or(&self.pbs),
+            &self.rivals,
+        )
+    }
+}
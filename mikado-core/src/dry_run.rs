@@ -0,0 +1,54 @@
+use crate::types::tachi::Import;
+use crate::types::user::User;
+use anyhow::Result;
+use log::info;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Where imports are written instead of being sent to Tachi.
+const DRY_RUN_DIRECTORY: &str = "mikado-dry-run";
+
+/// Tells apart imports written within the same millisecond.
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+pub fn is_enabled() -> bool {
+    crate::configuration().general.dry_run
+}
+
+/// Writes the import the user would have sent to Tachi to its own file.
+pub fn write_import(user: &User, import: &Import) -> Result<()> {
+    std::fs::create_dir_all(DRY_RUN_DIRECTORY)
+        .map_err(|err| anyhow::anyhow!("Could not create {DRY_RUN_DIRECTORY} folder: {}", err))?;
+
+    let path = Path::new(DRY_RUN_DIRECTORY).join(format!(
+        "{}-{}-{:06}.json",
+        user.card_id,
+        chrono::Local::now().format("%Y%m%d-%H%M%S%3f"),
+        SEQUENCE.fetch_add(1, Ordering::Relaxed)
+    ));
+    let json = serde_json::to_vec_pretty(import)?;
+    std::fs::write(&path, json)
+        .map_err(|err| anyhow::anyhow!("Could not write {}: {}", path.display(), err))?;
+
+    info!(
+        "Dry run, wrote {} score(s) for profile \"{}\" to {}",
+        import.scores.len(),
+        user.profile.name,
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// The Tachi PBs response injected in place of the one from the PBs endpoint.
+pub fn pbs() -> Result<serde_json::Value> {
+    let path = crate::configuration().general.dry_run_pbs.clone();
+    let content = std::fs::read(&path).map_err(|err| {
+        anyhow::anyhow!(
+            "Could not read PBs fixture {path}, save the response of the Tachi PBs endpoint there: {err}"
+        )
+    })?;
+
+    serde_json::from_slice(&content)
+        .map_err(|err| anyhow::anyhow!("Could not parse PBs fixture {path}: {err}"))
+}
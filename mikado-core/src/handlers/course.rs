@@ -0,0 +1,56 @@
+use crate::hook;
+use crate::types::game::{GameCourse, Track};
+use crate::types::tachi::{TachiDifficulty, TachiLamp};
+use anyhow::Result;
+use log::{error, info};
+use std::sync::Mutex;
+
+/// Tracks of the skill analyzer run in progress, sent together by `save_m` before `save_c`.
+static STAGES: Mutex<Vec<Track>> = Mutex::new(vec![]);
+
+pub fn record_stages(tracks: &[Track]) {
+    match STAGES.lock() {
+        Ok(mut stages) => *stages = tracks.to_vec(),
+        Err(err) => error!("Course stages Mutex is poisoned: {err:#}"),
+    }
+}
+
+/// Tachi has no notion of courses, so the run is only summarized here. The skill level it grants
+/// is exported with `save` like any other class update.
+pub fn process_course(course: GameCourse) -> Result<()> {
+    let stages = STAGES
+        .lock()
+        .map(|mut stages| std::mem::take(&mut *stages))
+        .map_err(|err| anyhow::anyhow!("Course stages Mutex is poisoned: {err:#}"))?;
+
+    let version = hook::GAME_PROPERTIES
+        .get()
+        .map(|p| p.version())
+        .unwrap_or_default();
+
+    info!(
+        "Skill analyzer course {} (season {}) {}: score {}, EX score {}, gauge {:.2}%",
+        course.course_id,
+        course.season_id,
+        if course.is_passed() {
+            "passed"
+        } else {
+            "failed"
+        },
+        course.score,
+        course.ex_score,
+        course.achievement_rate as f32 / 100.0
+    );
+    for (index, track) in stages.iter().enumerate() {
+        info!(
+            "Stage {}: chart {} [{}] {} {}",
+            index + 1,
+            track.music_id,
+            TachiDifficulty::from(track.music_type).name(),
+            track.score,
+            TachiLamp::from_clear_type(version, track.clear_type).name()
+        );
+    }
+
+    Ok(())
+}
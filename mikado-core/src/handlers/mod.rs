@@ -0,0 +1,20 @@
+use crate::configuration::SubmissionMode;
+use crate::types::tachi::Import;
+use crate::types::user::User;
+use crate::{batch, dry_run, queue};
+use anyhow::Result;
+
+pub mod course;
+pub mod save;
+pub mod scores;
+
+fn submit(user: &User, import: Import) -> Result<()> {
+    if dry_run::is_enabled() {
+        return dry_run::write_import(user, &import);
+    }
+
+    match crate::configuration().general.submission {
+        SubmissionMode::Live => queue::submit(user, import),
+        SubmissionMode::BatchManual => batch::append(user, import),
+    }
+}
@@ -1,6 +1,7 @@
+use crate::types::GameVersion;
 use crate::types::game::GameSave;
 use crate::types::tachi::{Import, ImportClasses, ImportMeta, SkillLevel};
-use crate::{TACHI_IMPORT_URL, helpers, mikado};
+use crate::{helpers, hook, session};
 use anyhow::Result;
 use log::info;
 
@@ -15,26 +16,29 @@ pub fn process_save(save: GameSave) -> Result<()> {
         return Ok(());
     };
 
-    let version = mikado::GAME_PROPERTIES
+    let class = SkillLevel::from(save.skill_level);
+    session::record_class(&user.card_id, class);
+    if !crate::configuration().general.export_class {
+        return Ok(());
+    }
+
+    let version = hook::GAME_PROPERTIES
         .get()
         .map(|p| p.version())
         .unwrap_or_default();
 
-    let import = Import {
+    super::submit(&user, build_import(version, &save))?;
+
+    Ok(())
+}
+
+/// The import `process_save` would submit, without sending anything.
+pub fn build_import(version: GameVersion, save: &GameSave) -> Import {
+    Import {
         meta: ImportMeta::new(version),
         classes: Some(ImportClasses {
             dan: SkillLevel::from(save.skill_level),
         }),
         scores: vec![],
-    };
-
-    helpers::call_tachi(
-        "POST",
-        TACHI_IMPORT_URL.as_str(),
-        &user.profile.api_key,
-        Some(import),
-    )?;
-    info!("Successfully updated class for card {}", user.card_id);
-
-    Ok(())
+    }
 }
@@ -0,0 +1,185 @@
+use super::course;
+use crate::types::GameVersion;
+use crate::types::game::{GameScores, Track};
+use crate::types::tachi::{Import, ImportMeta, ImportScore};
+use crate::{helpers, history, hook, pbs, session, volforce};
+use anyhow::Result;
+use either::Either;
+use log::{error, info};
+
+/// `seen_at` is when the hook intercepted the property, used for tracks that carry no time.
+pub fn process_scores(scores: GameScores, seen_at: u128) -> Result<()> {
+    let in_skill_analyzer = scores.tracks.is_right();
+    let mut tracks = match scores.tracks {
+        Either::Left(track) => vec![track],
+        // Only a skill analyzer run sends several tracks at once
+        Either::Right(tracks) => {
+            course::record_stages(&tracks);
+            tracks
+        }
+    };
+    stamp(&mut tracks, seen_at);
+
+    let version = hook::GAME_PROPERTIES
+        .get()
+        .map(|p| p.version())
+        .unwrap_or_default();
+
+    let user = helpers::get_current_user();
+
+    // Recorded before any of the checks below so that the history also has the plays that are
+    // not submitted
+    let card_id = scores
+        .ref_id
+        .as_ref()
+        .and_then(|_| helpers::get_current_card());
+    let profile = card_id.as_ref().and_then(helpers::get_profile);
+    if let Err(err) = history::record(
+        card_id.as_deref(),
+        profile.as_ref().map(|profile| profile.name.as_str()),
+        version,
+        &tracks,
+    ) {
+        error!("{err:#}");
+    }
+
+    if scores.ref_id.is_none() {
+        info!("Guest play, skipping score(s) submission");
+        return Ok(());
+    }
+
+    let Some(user) = user else {
+        info!("User is not set, skipping score(s) submission");
+        return Ok(());
+    };
+
+    let improvements = pbs::update(&user.card_id, version, &tracks);
+    pbs::announce(&improvements);
+    session::record_tracks(&user.card_id, version, &tracks, &improvements);
+    volforce::update(&user.card_id, version, &tracks);
+
+    let import = import(version, &tracks, in_skill_analyzer, seen_at);
+    super::submit(&user, import)?;
+
+    Ok(())
+}
+
+/// The import `process_scores` would submit, without recording or sending anything.
+pub fn build_import(version: GameVersion, scores: GameScores, seen_at: u128) -> Import {
+    let in_skill_analyzer = scores.tracks.is_right();
+    let mut tracks = scores.tracks.either(|track| vec![track], |tracks| tracks);
+    stamp(&mut tracks, seen_at);
+
+    import(version, &tracks, in_skill_analyzer, seen_at)
+}
+
+/// Gives the tracks that carry no time one from `seen_at`.
+///
+/// The stages of a skill analyzer run are all sent at the end, each one is set a millisecond
+/// before the next so that Tachi sees them as distinct plays, in the order they were played.
+fn stamp(tracks: &mut [Track], seen_at: u128) {
+    let stages = tracks.len() as u128;
+    for (stage, track) in tracks.iter_mut().enumerate() {
+        let offset = stages - 1 - stage as u128;
+        track
+            .time_achieved
+            .get_or_insert(seen_at.saturating_sub(offset));
+    }
+}
+
+fn import(
+    version: GameVersion,
+    tracks: &[Track],
+    in_skill_analyzer: bool,
+    seen_at: u128,
+) -> Import {
+    let scores = tracks
+        .iter()
+        .map(|track| {
+            ImportScore::from_track(
+                version,
+                track,
+                track.time_achieved.unwrap_or(seen_at),
+                Some(in_skill_analyzer),
+            )
+        })
+        .collect();
+
+    Import {
+        meta: ImportMeta::new(version),
+        classes: None,
+        scores,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEN_AT: u128 = 1_700_000_000_000;
+
+    fn track(music_id: u32) -> Track {
+        Track {
+            music_id,
+            music_type: 3,
+            score: 9_500_000,
+            clear_type: 2,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn skill_analyzer_stages_get_their_own_time() {
+        let scores = GameScores {
+            ref_id: Some("REFID".to_string()),
+            tracks: Either::Right(vec![track(1), track(2), track(3)]),
+        };
+
+        let import = build_import(GameVersion::ExceedGear, scores, SEEN_AT);
+        let times = import
+            .scores
+            .iter()
+            .map(|score| score.time_achieved)
+            .collect::<Vec<_>>();
+        assert_eq!(times, [SEEN_AT - 2, SEEN_AT - 1, SEEN_AT]);
+    }
+
+    #[test]
+    fn kept_time_is_not_replaced() {
+        let mut tracks = vec![track(1), track(2)];
+        tracks[0].time_achieved = Some(42);
+        stamp(&mut tracks, SEEN_AT);
+
+        assert_eq!(tracks[0].time_achieved, Some(42));
+        assert_eq!(tracks[1].time_achieved, Some(SEEN_AT));
+    }
+
+    #[test]
+    fn builds_import_for_each_version() {
+        for (version, id, lamp) in [
+            (GameVersion::ExceedGear, "exceed", "ULTIMATE CHAIN"),
+            (GameVersion::Nabla, "nabla", "MAXXIVE CLEAR"),
+        ] {
+            let scores = GameScores {
+                ref_id: Some("REFID".to_string()),
+                tracks: Either::Left(Track {
+                    clear_type: 4,
+                    ..track(1500)
+                }),
+            };
+
+            let import = serde_json::to_value(build_import(version, scores, SEEN_AT)).unwrap();
+            assert_eq!(
+                import["meta"],
+                serde_json::json!({ "game": "sdvx", "service": "Mikado", "version": id })
+            );
+            assert_eq!(import.get("classes"), None);
+            let scores = import["scores"].as_array().unwrap();
+            assert_eq!(scores.len(), 1);
+            assert_eq!(scores[0]["identifier"], "1500");
+            assert_eq!(scores[0]["lamp"], lamp);
+            assert_eq!(scores[0]["timeAchieved"], SEEN_AT as u64);
+            assert_eq!(scores[0]["scoreMeta"]["inSkillAnalyser"], false);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::hook::{CURRENT_CARD, CURRENT_USER};
+use crate::types::tachi::{
+    Import, ImportDocument, ImportResponse, ImportStatus, TachiError, TachiResponse,
+};
+use crate::types::user::{DEFAULT_TARGET, Profile, Target, User};
+use crate::{card, dry_run};
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+static USER_AGENT: std::sync::LazyLock<String> = std::sync::LazyLock::new(|| {
+    format!(
+        "mikado-{}/{}",
+        env!("CARGO_PKG_VERSION"),
+        option_env!("VERGEN_GIT_DESCRIBE").unwrap_or("unknown")
+    )
+});
+
+const IMPORT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+const IMPORT_POLL_ATTEMPTS: u32 = 30;
+
+pub fn request_agent() -> ureq::Agent {
+    agent(true)
+}
+
+fn agent(http_status_as_error: bool) -> ureq::Agent {
+    let timeout = crate::configuration().general.timeout;
+    let timeout = if timeout > 10000 { 10000 } else { timeout };
+
+    let config = ureq::Agent::config_builder()
+        .timeout_global(Some(std::time::Duration::from_millis(timeout)))
+        .user_agent(USER_AGENT.as_str())
+        .http_status_as_error(http_status_as_error)
+        .build();
+
+    ureq::Agent::new_with_config(config)
+}
+
+fn request<T>(
+    method: impl AsRef<str>,
+    url: impl AsRef<str>,
+    key: impl AsRef<str>,
+    body: Option<T>,
+) -> Result<ureq::http::Response<ureq::Body>>
+where
+    T: Serialize + Debug,
+{
+    // Statuses are checked by hand so that the Retry-After header can be read
+    let agent = agent(false);
+
+    let method = method.as_ref();
+    let url = url.as_ref();
+    if dry_run::is_enabled() {
+        info!("Dry run, not sending {method} request to {url} with body: {body:#?}");
+        return Err(anyhow::anyhow!("Tachi is never contacted in dry-run mode"));
+    }
+    debug!("{method} request to {url} with body: {body:#?}");
+
+    let authorization = format!("Bearer {}", key.as_ref());
+    let request = ureq::http::Request::builder()
+        .method(method)
+        .uri(url)
+        .header("Authorization", authorization.as_str());
+    let mut response = match body {
+        Some(body) => {
+            let json = serde_json::to_vec(&body)?;
+            let request = request
+                .header("Content-Type", "application/json")
+                .body(json)?;
+            agent.run(request)
+        }
+        None => {
+            let request = request.body(())?;
+            agent.run(request)
+        }
+    }
+    .map_err(|err| TachiError::Unreachable(format!("{err:#}")))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let err = match status.as_u16() {
+        401 | 403 => TachiError::Unauthorized(status.as_u16()),
+        429 => TachiError::RateLimited(
+            response
+                .headers()
+                .get("Retry-After")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(std::time::Duration::from_secs),
+        ),
+        500..=599 => TachiError::Server(status.as_u16()),
+        _ => {
+            // Tachi explains what was wrong with the request in the description field
+            let description = response
+                .body_mut()
+                .read_json::<TachiResponse<serde_json::Value>>()
+                .map(|response| response.description)
+                .unwrap_or_else(|_| format!("HTTP {status}"));
+            TachiError::Rejected(description)
+        }
+    };
+
+    Err(err.into())
+}
+
+/// Posts an import and waits for Tachi to process it.
+///
+/// Returns `None` if Tachi deferred the import and it was still not processed after polling for a
+/// while, it has been accepted at this point so it must not be sent again.
+pub fn import_to_tachi(
+    url: impl AsRef<str>,
+    key: impl AsRef<str>,
+    import: &Import,
+) -> Result<Option<ImportDocument>> {
+    let key = key.as_ref();
+    let response: TachiResponse<ImportResponse> = request_tachi("POST", url, key, Some(import))?;
+
+    let deferred = match response.into_body()? {
+        ImportResponse::Completed(document) => return Ok(Some(document)),
+        ImportResponse::Deferred(deferred) => deferred,
+    };
+
+    debug!(
+        "Tachi deferred import {}, polling its status",
+        deferred.import_id
+    );
+    for _ in 0..IMPORT_POLL_ATTEMPTS {
+        std::thread::sleep(IMPORT_POLL_INTERVAL);
+
+        let response: TachiResponse<ImportStatus> =
+            request_tachi("GET", &deferred.url, key, None::<()>)?;
+        match response.into_body()? {
+            ImportStatus::Completed { import } => return Ok(Some(import)),
+            ImportStatus::Ongoing { progress } => {
+                if let Some(progress) = progress {
+                    debug!(
+                        "Import {} is ongoing: {}",
+                        deferred.import_id, progress.description
+                    );
+                }
+            }
+        }
+    }
+
+    warn!(
+        "Tachi is still processing import {}, giving up on waiting for it",
+        deferred.import_id
+    );
+
+    Ok(None)
+}
+
+pub fn request_tachi<T, R>(
+    method: impl AsRef<str>,
+    url: impl AsRef<str>,
+    key: impl AsRef<str>,
+    body: Option<T>,
+) -> Result<R>
+where
+    T: Serialize + Debug,
+    R: for<'de> Deserialize<'de> + Debug,
+{
+    let mut response = request(method, url, key, body)?;
+    let response = response
+        .body_mut()
+        .read_json()
+        .map_err(|err| TachiError::MalformedBody(format!("{err:#}")))?;
+    debug!("Tachi API response: {response:#?}");
+
+    Ok(response)
+}
+
+pub fn get_current_user() -> Option<User> {
+    let guard = CURRENT_USER.read().unwrap_or_else(|err| {
+        error!("Current user RwLock is poisoned: {err:#}");
+        err.into_inner()
+    });
+
+    guard.clone()
+}
+
+pub fn get_current_card() -> Option<String> {
+    let guard = CURRENT_CARD.read().unwrap_or_else(|err| {
+        error!("Current card RwLock is poisoned: {err:#}");
+        err.into_inner()
+    });
+
+    guard.clone()
+}
+
+pub fn get_profile(card: impl AsRef<str>) -> Option<Profile> {
+    let configuration = crate::configuration();
+    let card = card::normalize(card.as_ref())?;
+
+    // find the card in a profile ...
+    configuration
+        .card_profiles
+        .get(&card)
+        .cloned()
+        // ... or fallback to single-user config, if present
+        .or_else(|| {
+            let cards_config = configuration.cards.as_ref()?;
+            let api_key = configuration.tachi.api_key.as_ref()?;
+
+            let is_whitelisted =
+                cards_config.whitelist.is_empty() || configuration.whitelist.contains(&card);
+
+            is_whitelisted.then(|| Profile {
+                name: "default".to_string(),
+                targets: vec![Target {
+                    name: DEFAULT_TARGET.to_string(),
+                    api_key: api_key.clone(),
+                    urls: configuration.default_tachi_urls.clone(),
+                }],
+                primary: 0,
+            })
+        })
+}
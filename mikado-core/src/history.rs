@@ -0,0 +1,196 @@
+use crate::configuration::HistoryExport;
+use crate::hook;
+use crate::types::GameVersion;
+use crate::types::game::Track;
+use crate::types::tachi::{Import, ImportMeta, ImportScore, TachiDifficulty, TachiLamp};
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Every play seen by the hook, one JSON object per line, whether it was submitted or not.
+pub const HISTORY_PATH: &str = "mikado.history.jsonl";
+const CSV_EXPORT_PATH: &str = "mikado.history.csv";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub time_achieved: u128,
+    /// `None` for guest plays.
+    pub card_id: Option<String>,
+    /// `None` if the card is not associated with any profile.
+    pub profile: Option<String>,
+    pub version: String,
+    pub datecode: u64,
+    pub track: Track,
+}
+
+pub fn record(
+    card_id: Option<&str>,
+    profile: Option<&str>,
+    version: GameVersion,
+    tracks: &[Track],
+) -> Result<()> {
+    let datecode = hook::GAME_PROPERTIES
+        .get()
+        .map(|p| p.ext())
+        .unwrap_or_default();
+
+    let mut lines = Vec::new();
+    for track in tracks {
+        let record = HistoryRecord {
+            time_achieved: track.time_achieved.unwrap_or_default(),
+            card_id: card_id.map(str::to_string),
+            profile: profile.map(str::to_string),
+            version: version.tachi_id().to_string(),
+            datecode,
+            track: track.clone(),
+        };
+        serde_json::to_writer(&mut lines, &record)?;
+        lines.push(b'\n');
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(HISTORY_PATH)
+        .and_then(|mut file| file.write_all(&lines))
+        .map_err(|err| anyhow::anyhow!("Could not write score history: {}", err))
+}
+
+pub fn load() -> Result<Vec<HistoryRecord>> {
+    if !Path::new(HISTORY_PATH).exists() {
+        return Ok(vec![]);
+    }
+
+    let file = File::open(HISTORY_PATH)
+        .map_err(|err| anyhow::anyhow!("Could not open score history: {}", err))?;
+
+    let mut records = vec![];
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<HistoryRecord>(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => warn!(
+                "Ignoring malformed record on line {} of {HISTORY_PATH}: {err:#}",
+                index + 1
+            ),
+        }
+    }
+
+    Ok(records)
+}
+
+pub fn export(formats: &[HistoryExport]) -> Result<()> {
+    let records = load()?;
+    for format in formats {
+        match format {
+            HistoryExport::Csv => export_csv(&records)?,
+            HistoryExport::BatchManual => export_batch_manual(&records)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn export_csv(records: &[HistoryRecord]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(CSV_EXPORT_PATH)?);
+    writeln!(
+        writer,
+        "time,card,profile,version,datecode,music_id,difficulty,score,ex_score,lamp,max_chain,critical,near,error,gauge_type,effective_rate,judge"
+    )?;
+
+    for record in records {
+        let version = GameVersion::from_tachi_id(&record.version).unwrap_or_default();
+        let track = &record.track;
+        let time = chrono::DateTime::from_timestamp_millis(record.time_achieved as i64)
+            .map(|time| time.with_timezone(&chrono::Local).to_rfc3339())
+            .unwrap_or_default();
+        let judge = track
+            .judge
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            time,
+            csv_field(record.card_id.as_deref().unwrap_or("guest")),
+            csv_field(record.profile.as_deref().unwrap_or("")),
+            record.version,
+            record.datecode,
+            track.music_id,
+            TachiDifficulty::from(track.music_type).name(),
+            track.score,
+            track.ex_score,
+            TachiLamp::from_clear_type(version, track.clear_type).name(),
+            track.max_chain,
+            track.critical,
+            track.near,
+            track.error,
+            track.gauge_type,
+            track.effective_rate,
+            judge
+        )?;
+    }
+    writer.flush()?;
+
+    info!("Exported {} score(s) to {CSV_EXPORT_PATH}", records.len());
+
+    Ok(())
+}
+
+/// Writes one file per card and game version, which is how Tachi expects BATCH-MANUAL imports.
+fn export_batch_manual(records: &[HistoryRecord]) -> Result<()> {
+    let mut imports: BTreeMap<(&str, &str), Vec<ImportScore>> = BTreeMap::new();
+    for record in records {
+        let Some(card_id) = &record.card_id else {
+            continue;
+        };
+        let Some(version) = GameVersion::from_tachi_id(&record.version) else {
+            continue;
+        };
+
+        imports
+            .entry((card_id, &record.version))
+            .or_default()
+            .push(ImportScore::from_track(
+                version,
+                &record.track,
+                record.time_achieved,
+                None,
+            ));
+    }
+
+    for ((card_id, version), scores) in imports {
+        let path = format!("mikado.history.{card_id}.{version}.json");
+        let count = scores.len();
+        let version = GameVersion::from_tachi_id(version).unwrap_or_default();
+        let import = Import {
+            meta: ImportMeta::batch_manual(version),
+            classes: None,
+            scores,
+        };
+        let mut writer = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer_pretty(&mut writer, &import)?;
+        writer.flush()?;
+        info!("Exported {count} score(s) to {path}");
+    }
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,615 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+
+use anyhow::Result;
+use bytes::Bytes;
+use kbinxml::{CompressionType, EncodingType, Node, Options, Value};
+use log::{debug, error, info, warn};
+
+use crate::configuration::SubmissionMode;
+use crate::handlers::course::process_course;
+use crate::handlers::save::process_save;
+use crate::handlers::scores::process_scores;
+use crate::property::PropertyApi;
+use crate::types::GameProperties;
+use crate::types::game::{Game, Property};
+use crate::types::user::{Target, User};
+use crate::{capture, helpers, history, queue, session};
+
+pub static CURRENT_USER: RwLock<Option<User>> = RwLock::new(None);
+/// Last card seen by `cardmng inquire`, even if it is not whitelisted.
+pub static CURRENT_CARD: RwLock<Option<String>> = RwLock::new(None);
+pub static GAME_PROPERTIES: OnceLock<GameProperties> = OnceLock::new();
+
+/// Starts the workers once the game is hooked.
+pub fn start() {
+    let configuration = crate::configuration();
+    if configuration.general.dry_run {
+        info!(
+            "Dry run enabled, imports will be written to mikado-dry-run and Tachi will not be contacted"
+        );
+    } else if configuration.general.submission == SubmissionMode::Live {
+        if let Err(err) = queue::start() {
+            error!("Could not start import queue, failed imports will not be retried: {err:#}");
+        }
+    } else {
+        info!("Batch-manual mode enabled, scores will not be sent to Tachi");
+    }
+
+    if let Err(err) = crate::watch_configuration() {
+        error!("{err:#}");
+    }
+}
+
+/// Flushes what is left before the game exits.
+///
+/// Imports still on their way to Tachi are not waited for, they are already journaled and will
+/// be sent at next startup.
+pub fn stop() {
+    session::end();
+
+    let configuration = crate::configuration();
+    if !configuration.general.history_export.is_empty()
+        && let Err(err) = history::export(&configuration.general.history_export)
+    {
+        error!("Could not export score history: {err:#}");
+    }
+
+    queue::stop();
+}
+
+/// Points the current user at their profile in the configuration in use, called after a reload.
+pub fn refresh_current_user() {
+    let mut guard = CURRENT_USER.write().unwrap_or_else(|err| {
+        error!("Current user RwLock is poisoned: {err:#}");
+        err.into_inner()
+    });
+    let Some(user) = guard.as_mut() else {
+        return;
+    };
+
+    match helpers::get_profile(&user.card_id) {
+        Some(profile) => user.profile = profile,
+        None => {
+            warn!(
+                "Card {} has no profile anymore, its scores will not be submitted",
+                user.card_id
+            );
+            *guard = None;
+            drop(guard);
+            session::end();
+        }
+    }
+}
+
+static LOAD: AtomicBool = AtomicBool::new(false);
+static LOAD_M: AtomicBool = AtomicBool::new(false);
+static COMMON: AtomicBool = AtomicBool::new(false);
+static LOAD_R: AtomicBool = AtomicBool::new(false);
+
+/// Whether the next response may need to be modified, checked before copying it.
+pub fn awaiting_response() -> bool {
+    crate::configuration().general.capture
+        || LOAD.load(Ordering::SeqCst)
+        || LOAD_M.load(Ordering::SeqCst)
+        || COMMON.load(Ordering::SeqCst)
+        || LOAD_R.load(Ordering::SeqCst)
+}
+
+/// Returns the modified response, or `None` if `original` must be left as is.
+pub fn process_response(original: Vec<u8>) -> Option<Result<Vec<u8>>> {
+    let capture = crate::configuration().general.capture;
+    if capture {
+        capture::response("original", &original);
+    }
+
+    let response = property_mem_read_hook_wrapped(
+        original,
+        LOAD.load(Ordering::SeqCst),
+        LOAD_M.load(Ordering::SeqCst),
+        COMMON.load(Ordering::SeqCst),
+        LOAD_R.load(Ordering::SeqCst),
+    );
+    if capture && let Some(Ok(rewritten)) = &response {
+        capture::response("rewritten", rewritten);
+    }
+
+    response
+}
+
+fn build_response(
+    original_signature: &[u8],
+    response: Node,
+    encoding: EncodingType,
+) -> Result<Vec<u8>> {
+    if kbinxml::is_binary_xml(original_signature) {
+        let bytes = kbinxml::to_binary_with_options(
+            Options::new(CompressionType::from_byte(original_signature[1])?, encoding),
+            &response,
+        )?;
+        Ok(bytes)
+    } else {
+        let bytes = kbinxml::to_text_xml(&response)?;
+        Ok(bytes)
+    }
+}
+
+#[allow(clippy::manual_map)]
+fn property_mem_read_hook_wrapped(
+    original: Vec<u8>,
+    load: bool,
+    load_m: bool,
+    common: bool,
+    load_r: bool,
+) -> Option<Result<Vec<u8>>> {
+    let original_signature = original[..2].to_vec();
+    let (mut root, encoding) = kbinxml::from_bytes(Bytes::from(original))
+        .and_then(|(node, encoding)| node.as_node().map(|node| (node, encoding)))
+        .ok()?;
+
+    if common
+        .then(|| root.pointer(&["game", "event"]))
+        .flatten()
+        .is_some()
+    {
+        Some((|| {
+            let events = root
+                .pointer_mut(&["game", "event"])
+                .expect("Could not find events node");
+
+            events.children_mut().retain(|info| {
+                if let Some(Value::String(event_id)) = info
+                    .pointer(&["event_id"])
+                    .and_then(|event_id| event_id.value())
+                {
+                    event_id != "CLOUD_LINK_ENABLE"
+                } else {
+                    true
+                }
+            });
+            events.children_mut().push(Node::with_nodes(
+                "info",
+                vec![Node::with_value(
+                    "event_id",
+                    Value::String("CLOUD_LINK_ENABLE".to_string()),
+                )],
+            ));
+            let response = build_response(&original_signature, root, encoding)?;
+            COMMON.store(false, Ordering::Relaxed);
+
+            Ok(response)
+        })())
+    } else if helpers::get_current_user().is_some()
+        && load
+            .then(|| root.pointer(&["game", "code"]))
+            .flatten()
+            .is_some()
+    {
+        Some((|| {
+            let game = root
+                .pointer_mut(&["game"])
+                .expect("Could not find game node");
+            game.children_mut().retain(|node| node.key() != "cloud");
+            game.children_mut().push(Node::with_nodes(
+                "cloud",
+                vec![Node::with_value("relation", Value::S8(1))],
+            ));
+            let response = build_response(&original_signature, root, encoding)?;
+            LOAD.store(false, Ordering::Relaxed);
+
+            Ok(response)
+        })())
+    } else if let Some(music) = load_m.then(|| root.pointer(&["game", "music"])).flatten() {
+        if let Some(user) = helpers::get_current_user() {
+            Some((|| {
+                let response = crate::cloudlink::process_pbs(&user, music)?;
+                let response = build_response(&original_signature, response, encoding)?;
+                LOAD_M.store(false, Ordering::Relaxed);
+
+                Ok(response)
+            })())
+        } else {
+            None
+        }
+    } else if load_r && root.pointer(&["game"]).is_some() {
+        let user = helpers::get_current_user()?;
+        // Only the rivals loaded at card scan are served, Tachi is never waited for here
+        LOAD_R.store(false, Ordering::Relaxed);
+        let response = crate::cloudlink::process_rivals(&user)?;
+
+        Some(build_response(&original_signature, response, encoding))
+    } else {
+        None
+    }
+}
+
+/// Everything the hook does with a request before it is sent, whatever the property comes from.
+pub fn handle_property(property: &impl PropertyApi, seen_at: u128) {
+    if crate::configuration().general.capture {
+        capture::property(property);
+    }
+
+    let Some(name) = property.call_name() else {
+        return;
+    };
+    if name != "game" && name != "cardmng" {
+        return;
+    }
+
+    let Some(method) = property.call_attribute("method") else {
+        return;
+    };
+    debug!("Intercepted '{name}' method: {method}");
+
+    if name == "cardmng" {
+        if method != "inquire" {
+            return;
+        }
+
+        let Some(card_id) = property.call_attribute("cardid") else {
+            return;
+        };
+
+        if let Ok(mut guard) = CURRENT_CARD.write() {
+            *guard = Some(card_id.clone());
+        } else {
+            warn!("Could not acquire write lock on current card");
+        }
+
+        let profile = helpers::get_profile(&card_id);
+        if profile.is_none() {
+            warn!("No profile for card {card_id}");
+        }
+
+        // Try to reach Tachi API
+        fn get_tachi_user(target: &Target) -> Result<u64> {
+            let response: serde_json::Value =
+                helpers::request_tachi("GET", &target.urls.status, &target.api_key, None::<()>)?;
+
+            response["body"]["whoami"]
+                .as_u64()
+                .ok_or_else(|| anyhow::anyhow!("Couldn't parse user from Tachi response"))
+        }
+
+        let configuration = crate::configuration();
+        let tachi_id = if configuration.general.submission == SubmissionMode::BatchManual
+            || configuration.general.dry_run
+        {
+            // Tachi is not contacted, PBs are either disabled or read from the dry-run fixture
+            profile.as_ref().map(|_| 0)
+        } else {
+            profile
+                .as_ref()
+                .and_then(|profile| match get_tachi_user(profile.primary()) {
+                    Ok(user) => {
+                        debug!("Tachi API reached, set current user to {user}");
+                        Some(user)
+                    }
+                    Err(e) => {
+                        warn!("Could not get Tachi user for card {card_id}: {e:#}");
+                        None
+                    }
+                })
+        };
+
+        let user = tachi_id.and_then(|tachi_id| {
+            profile.map(|profile| {
+                info!(
+                    "Setting current profile to \"{}\": card is {}, tachi is {}",
+                    &profile.name, card_id, tachi_id
+                );
+                User {
+                    tachi_id,
+                    card_id,
+                    profile,
+                    session_started: chrono::Local::now(),
+                }
+            })
+        });
+
+        // Scanning a card ends the session of the previous player
+        match &user {
+            Some(user) => session::start(user),
+            None => session::end(),
+        }
+
+        // Otherwise they are loaded along with the cloud scores
+        let live = configuration.general.submission == SubmissionMode::Live;
+        if let Some(user) = &user
+            && live
+            && !configuration.general.inject_cloud_pbs
+        {
+            crate::cloudlink::preload_pbs(user);
+        }
+        if let Some(user) = &user
+            && live
+            && configuration.general.inject_rivals
+            && !configuration.general.dry_run
+        {
+            crate::cloudlink::prefetch_rivals(user);
+        }
+
+        if let Ok(mut guard) = CURRENT_USER.write() {
+            *guard = user;
+        } else {
+            warn!("Could not acquire write lock on current user");
+        }
+
+        return;
+    }
+
+    let prefix = GAME_PROPERTIES
+        .get()
+        .map(|p| p.version().method_prefix())
+        .unwrap_or("sv6");
+    let method = method
+        .strip_prefix(prefix)
+        .and_then(|s| s.strip_prefix('_'))
+        .unwrap_or("");
+
+    let configuration = crate::configuration();
+    // The response hook may also be running to capture traffic, Tachi must not be reached offline
+    let live = configuration.general.submission == SubmissionMode::Live;
+    if configuration.general.inject_cloud_pbs && live {
+        if method == "load_m" {
+            LOAD_M.store(true, Ordering::Relaxed);
+        } else if method == "common" {
+            COMMON.store(true, Ordering::Relaxed);
+        } else if method == "load" {
+            LOAD.store(true, Ordering::Relaxed);
+        }
+    }
+    if configuration.general.inject_rivals
+        && live
+        && !configuration.general.dry_run
+        && method == "load_r"
+    {
+        LOAD_R.store(true, Ordering::Relaxed);
+    }
+
+    // `save` is always read as it ends the session, even if the class is not exported
+    if method != "save_m" && method != "save_c" && method != "save" {
+        return;
+    }
+
+    let property_str = match property.to_json() {
+        Ok(property_str) => property_str,
+        Err(err) => {
+            error!("{err:#}");
+            return;
+        }
+    };
+
+    debug!("Processing property: {property_str}");
+    if let Err(err) = serde_json::from_str::<Property>(&property_str)
+        .map_err(|err| anyhow::anyhow!("Could not parse property: {err:#}"))
+        .and_then(|prop| match (method, prop.call.game) {
+            ("save_m", Game::Scores(scores)) => process_scores(scores, seen_at),
+            ("save", Game::Save(save)) => process_save(save),
+            ("save_c", Game::Course(course)) => process_course(course),
+            _ => Err(anyhow::anyhow!("Could not process {method} property")),
+        })
+    {
+        error!("{err:#}");
+    }
+
+    if method == "save" {
+        session::end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::{ActiveConfiguration, GeneralConfiguration};
+    use crate::property::MemoryProperty;
+    use crate::testing::{self, MockTachi};
+    use std::time::{Duration, Instant};
+
+    /// How long the mock Tachi takes to answer an import.
+    const IMPORT_DELAY: Duration = Duration::from_secs(2);
+
+    fn reset() {
+        *CURRENT_USER.write().unwrap() = None;
+        *CURRENT_CARD.write().unwrap() = None;
+        for flag in [&LOAD, &LOAD_M, &COMMON, &LOAD_R] {
+            flag.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn mock_tachi() -> MockTachi {
+        MockTachi::start(|request| match request.path.as_str() {
+            "/status" => (
+                Duration::ZERO,
+                testing::tachi_response(serde_json::json!({ "whoami": 42 })),
+            ),
+            "/import" => (
+                IMPORT_DELAY,
+                testing::tachi_response(
+                    serde_json::json!({ "importID": "test", "scoreIDs": ["1"] }),
+                ),
+            ),
+            _ => (
+                Duration::ZERO,
+                testing::tachi_response(serde_json::json!({ "pbs": [], "charts": [] })),
+            ),
+        })
+    }
+
+    fn replay(property: kbinxml::Node) {
+        handle_property(&MemoryProperty::new(property), 1_700_000_000_000);
+    }
+
+    #[test]
+    fn save_m_and_exit_do_not_wait_for_tachi() {
+        let _guard = testing::isolate();
+        reset();
+        let tachi = mock_tachi();
+        testing::configure(&tachi.base_url, GeneralConfiguration::default());
+        queue::start().unwrap();
+
+        replay(testing::inquire());
+        assert!(helpers::get_current_user().is_some());
+
+        let started = Instant::now();
+        replay(testing::save_m(1234, 9_900_000));
+        stop();
+        assert!(
+            started.elapsed() < IMPORT_DELAY / 2,
+            "hook waited {:?} for Tachi",
+            started.elapsed()
+        );
+        // Journaled before the worker picked it up, so it survives the game closing right away
+        assert_eq!(journal().len(), 1);
+
+        let import = tachi
+            .wait_for("/import", Duration::from_secs(5))
+            .expect("import was never sent");
+        assert_eq!(import.method, "POST");
+        assert!(import.body.contains("\"1234\""), "{}", import.body);
+
+        // Lets the worker hear back from Tachi and clear the journal before the next test
+        let deadline = Instant::now() + IMPORT_DELAY * 3;
+        while testing::working_file("mikado.queue.jsonl").exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn reload_updates_current_profile() {
+        let _guard = testing::isolate();
+        reset();
+        let tachi = mock_tachi();
+        testing::configure(&tachi.base_url, GeneralConfiguration::default());
+        replay(testing::inquire());
+
+        let other = MockTachi::start(|_| (Duration::ZERO, String::new()));
+        testing::configure(&other.base_url, GeneralConfiguration::default());
+        refresh_current_user();
+        let user = helpers::get_current_user().unwrap();
+        assert!(
+            user.profile
+                .primary()
+                .urls
+                .import
+                .starts_with(&other.base_url)
+        );
+
+        let mut configuration = (**crate::configuration()).clone();
+        configuration.profiles.clear();
+        crate::set_configuration(ActiveConfiguration::new(configuration).unwrap());
+        refresh_current_user();
+        assert!(helpers::get_current_user().is_none());
+    }
+
+    fn journal() -> Vec<queue::PendingImport> {
+        std::fs::read_to_string(testing::working_file("mikado.queue.jsonl"))
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn flags() -> [bool; 4] {
+        [&LOAD, &LOAD_M, &COMMON, &LOAD_R].map(|flag| flag.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn replays_a_credit() {
+        let _guard = testing::isolate();
+        reset();
+        // Without a worker imports stay in the journal, which is what is checked here
+        queue::stop();
+        let tachi = mock_tachi();
+        testing::configure(
+            &tachi.base_url,
+            GeneralConfiguration {
+                inject_cloud_pbs: true,
+                ..Default::default()
+            },
+        );
+
+        replay(testing::inquire());
+        let user = helpers::get_current_user().expect("user was not set");
+        assert_eq!(user.tachi_id, 42);
+        assert_eq!(user.card_id, testing::CARD_ID);
+        assert_eq!(user.profile.name, "test");
+        assert_eq!(tachi.requests().len(), 1);
+        assert_eq!(tachi.requests()[0].path, "/status");
+        assert_eq!(flags(), [false; 4]);
+
+        replay(testing::game_call("sv6_common", vec![]));
+        replay(testing::game_call("sv6_load", vec![]));
+        replay(testing::game_call("sv6_load_m", vec![]));
+        assert_eq!(flags(), [true, true, true, false]);
+        assert!(journal().is_empty());
+
+        replay(testing::save_m(1500, 9_800_000));
+        let journal = journal();
+        assert_eq!(journal.len(), 1);
+        let pending = &journal[0];
+        assert_eq!(pending.card_id, testing::CARD_ID);
+        assert_eq!(pending.target.as_deref(), Some("default"));
+        assert_eq!(pending.import.scores.len(), 1);
+        let score = &pending.import.scores[0];
+        assert_eq!(score.identifier, "1500");
+        assert_eq!(score.score, 9_800_000);
+        assert_eq!(score.time_achieved, 1_700_000_000_000);
+        // Only the status was asked for, scores go through the queue
+        assert_eq!(tachi.requests().len(), 1);
+    }
+
+    #[test]
+    fn replays_a_credit_in_dry_run() {
+        let _guard = testing::isolate();
+        reset();
+        queue::stop();
+        let tachi = mock_tachi();
+        testing::configure(
+            &tachi.base_url,
+            GeneralConfiguration {
+                inject_cloud_pbs: true,
+                dry_run: true,
+                ..Default::default()
+            },
+        );
+
+        replay(testing::inquire());
+        let user = helpers::get_current_user().expect("user was not set");
+        assert_eq!(user.tachi_id, 0);
+
+        replay(testing::game_call("sv6_load_m", vec![]));
+        assert_eq!(flags(), [false, true, false, false]);
+
+        replay(testing::save_m(1500, 9_800_000));
+        assert!(journal().is_empty());
+        let written = std::fs::read_dir(testing::working_file("mikado-dry-run"))
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(written.len(), 1);
+        let import = serde_json::from_str::<serde_json::Value>(&written[0]).unwrap();
+        assert_eq!(import["scores"][0]["identifier"], "1500");
+        assert!(tachi.requests().is_empty());
+    }
+
+    #[test]
+    fn unknown_card_has_no_user() {
+        let _guard = testing::isolate();
+        reset();
+        let tachi = mock_tachi();
+        testing::configure(&tachi.base_url, GeneralConfiguration::default());
+
+        let mut cardmng = kbinxml::Node::with_nodes("cardmng", vec![]);
+        cardmng.set_attr("method", "inquire");
+        cardmng.set_attr("cardid", "E004000000000000");
+        replay(kbinxml::Node::with_nodes("call", vec![cardmng]));
+
+        assert!(helpers::get_current_user().is_none());
+        assert_eq!(
+            CURRENT_CARD.read().unwrap().as_deref(),
+            Some("E004000000000000")
+        );
+        assert!(tachi.requests().is_empty());
+    }
+}
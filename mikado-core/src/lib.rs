@@ -0,0 +1,125 @@
+pub mod batch;
+pub mod capture;
+pub mod card;
+pub mod cloudlink;
+pub mod configuration;
+pub mod dry_run;
+pub mod handlers;
+pub mod helpers;
+pub mod history;
+pub mod hook;
+pub mod pbs;
+pub mod property;
+pub mod queue;
+pub mod redact;
+pub mod session;
+#[cfg(test)]
+mod testing;
+pub mod types;
+pub mod volforce;
+
+use configuration::{ActiveConfiguration, CONFIGURATION_PATH, Configuration};
+use log::{error, info, warn};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+
+static CONFIGURATION: OnceLock<RwLock<Arc<ActiveConfiguration>>> = OnceLock::new();
+
+/// Loaded from `mikado.toml` on first use, unless [`set_configuration`] was called before.
+fn configuration_lock() -> &'static RwLock<Arc<ActiveConfiguration>> {
+    CONFIGURATION.get_or_init(|| {
+        let configuration = Configuration::load()
+            .and_then(ActiveConfiguration::new)
+            .unwrap_or_else(|err| {
+                error!("{err:#}");
+                error!(
+                    "Mikado is disabled, fix {CONFIGURATION_PATH} and restart the game to enable it"
+                );
+                ActiveConfiguration::disabled()
+            });
+
+        RwLock::new(Arc::new(configuration))
+    })
+}
+
+const CONFIGURATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Current configuration, callers should not hold on to it as it may be replaced at any time.
+pub fn configuration() -> Arc<ActiveConfiguration> {
+    configuration_lock()
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// Replaces the configuration, tools that do not run in the game folder call it before anything
+/// else so that `mikado.toml` is never created.
+pub fn set_configuration(configuration: ActiveConfiguration) {
+    let configuration = Arc::new(configuration);
+    let lock = CONFIGURATION.get_or_init(|| RwLock::new(configuration.clone()));
+    *lock.write().unwrap_or_else(|err| err.into_inner()) = configuration;
+}
+
+/// Reloads `mikado.toml` whenever it is modified. An invalid file is reported and the previous
+/// configuration is kept.
+pub fn watch_configuration() -> anyhow::Result<()> {
+    fn modified() -> Option<SystemTime> {
+        std::fs::metadata(CONFIGURATION_PATH)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    let mut last_modified = modified();
+    std::thread::Builder::new()
+        .name("mikado-config".to_string())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(CONFIGURATION_POLL_INTERVAL);
+
+                let modified = modified();
+                if modified.is_none() || modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+
+                let result = Configuration::load().and_then(|mut reloaded| {
+                    // These decide which hooks and workers were started, they can't change live
+                    let current = &configuration().general;
+                    let general = &mut reloaded.general;
+                    if general.enable != current.enable
+                        || general.inject_cloud_pbs != current.inject_cloud_pbs
+                        || general.inject_rivals != current.inject_rivals
+                        || general.submission != current.submission
+                        || general.capture != current.capture
+                        || general.dry_run != current.dry_run
+                    {
+                        warn!(
+                            "Changes to enable, inject_cloud_pbs, inject_rivals, submission, capture and dry_run will only apply after a restart"
+                        );
+                        general.enable = current.enable;
+                        general.inject_cloud_pbs = current.inject_cloud_pbs;
+                        general.inject_rivals = current.inject_rivals;
+                        general.submission = current.submission;
+                        general.capture = current.capture;
+                        general.dry_run = current.dry_run;
+                    }
+
+                    ActiveConfiguration::new(reloaded)
+                });
+                match result {
+                    Ok(configuration) => {
+                        *configuration_lock().write().unwrap_or_else(|err| err.into_inner()) =
+                            Arc::new(configuration);
+                        info!("Reloaded {CONFIGURATION_PATH}");
+                        hook::refresh_current_user();
+                    }
+                    Err(err) => {
+                        error!("Ignoring changes to {CONFIGURATION_PATH}: {err:#}");
+                    }
+                }
+            }
+        })
+        .map_err(|err| anyhow::anyhow!("Could not start configuration watcher: {}", err))?;
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use crate::cloudlink::Pb;
+use crate::types::GameVersion;
+use crate::types::cloudlink::Chart;
+use crate::types::game::Track;
+use crate::types::tachi::{TachiDifficulty, TachiLamp};
+use log::{error, info};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// PBs of the card being played, as Tachi sent them and updated with every play since.
+static PBS: Mutex<Option<PbTable>> = Mutex::new(None);
+
+struct PbTable {
+    card_id: String,
+    charts: HashMap<Chart, PersonalBest>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PersonalBest {
+    pub score: u32,
+    pub lamp: TachiLamp,
+}
+
+/// A play that beat the score or the lamp of its chart.
+#[derive(Debug, Clone, Serialize)]
+pub struct Improvement {
+    pub song_id: u32,
+    pub difficulty: TachiDifficulty,
+    /// `None` the first time the chart is played.
+    pub previous: Option<PersonalBest>,
+    pub current: PersonalBest,
+}
+
+impl Improvement {
+    /// Console line such as `NEW PB: +123,456 (9,812,345), lamp EXCESSIVE CLEAR → ULTIMATE CHAIN`.
+    pub fn announcement(&self) -> String {
+        let chart = format!("{} [{}]", self.song_id, self.difficulty.name());
+        let Some(previous) = self.previous else {
+            return format!(
+                "NEW PB: {}, lamp {}, first play of chart {chart}",
+                thousands(self.current.score),
+                self.current.lamp.name()
+            );
+        };
+
+        let mut changes = vec![];
+        if self.current.score > previous.score {
+            changes.push(format!(
+                "+{} ({})",
+                thousands(self.current.score - previous.score),
+                thousands(self.current.score)
+            ));
+        }
+        if self.current.lamp != previous.lamp {
+            changes.push(format!(
+                "lamp {} → {}",
+                previous.lamp.name(),
+                self.current.lamp.name()
+            ));
+        }
+
+        format!("NEW PB: {} on chart {chart}", changes.join(", "))
+    }
+}
+
+fn thousands(value: u32) -> String {
+    let digits = value.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+
+    formatted
+}
+
+/// Logs the new PBs of a play as soon as it is saved.
+pub fn announce(improvements: &[Improvement]) {
+    for improvement in improvements {
+        info!("{}", improvement.announcement());
+    }
+}
+
+/// Replaces the PBs with the ones Tachi has for the card.
+pub(crate) fn seed(card_id: &str, version: GameVersion, pbs: &[Pb]) {
+    let charts = pbs
+        .iter()
+        .map(|pb| {
+            let best = PersonalBest {
+                score: pb.score,
+                lamp: TachiLamp::from_clear_type(version, pb.lamp),
+            };
+            (pb.chart, best)
+        })
+        .collect();
+
+    match PBS.lock() {
+        Ok(mut table) => {
+            *table = Some(PbTable {
+                card_id: card_id.to_string(),
+                charts,
+            })
+        }
+        Err(err) => error!("PBs Mutex is poisoned: {err:#}"),
+    }
+}
+
+/// Whether PBs were loaded for the card, without them every play would look like a new PB.
+pub fn is_seeded(card_id: &str) -> bool {
+    PBS.lock()
+        .map(|table| table.as_ref().is_some_and(|table| table.card_id == card_id))
+        .unwrap_or(false)
+}
+
+/// Updates the PBs with the tracks and returns the ones that improved on them.
+pub fn update(card_id: &str, version: GameVersion, tracks: &[Track]) -> Vec<Improvement> {
+    let mut table = match PBS.lock() {
+        Ok(table) => table,
+        Err(err) => {
+            error!("PBs Mutex is poisoned: {err:#}");
+            return vec![];
+        }
+    };
+    let Some(table) = table.as_mut().filter(|table| table.card_id == card_id) else {
+        return vec![];
+    };
+
+    tracks
+        .iter()
+        .filter_map(|track| {
+            let chart = Chart {
+                song_id: track.music_id,
+                difficulty: track.music_type as u8,
+            };
+            let played = PersonalBest {
+                score: track.score,
+                lamp: TachiLamp::from_clear_type(version, track.clear_type),
+            };
+
+            let previous = table.charts.get(&chart).copied();
+            let current = match previous {
+                Some(previous) => PersonalBest {
+                    score: previous.score.max(played.score),
+                    lamp: if played.lamp.rank() > previous.lamp.rank() {
+                        played.lamp
+                    } else {
+                        previous.lamp
+                    },
+                },
+                None => played,
+            };
+            if previous == Some(current) {
+                return None;
+            }
+
+            table.charts.insert(chart, current);
+            Some(Improvement {
+                song_id: track.music_id,
+                difficulty: TachiDifficulty::from(track.music_type),
+                previous,
+                current,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+use anyhow::Result;
+use kbinxml::{Node, Value, ValueArray};
+use serde_json::Map;
+
+/// What the hook reads from an e-amusement request, so that the interception logic does not
+/// depend on avs2 and can run on any property.
+pub trait PropertyApi {
+    /// Name of the `/call/game` or `/call/cardmng` node, whichever is found first.
+    fn call_name(&self) -> Option<String>;
+
+    /// Attribute of the call node, like `method` or `cardid`.
+    fn call_attribute(&self, name: &str) -> Option<String>;
+
+    /// The whole property in the JSON form avs2 writes it in.
+    fn to_json(&self) -> Result<String>;
+
+    /// The whole property as XML or kbin, whichever avs2 writes by default.
+    fn to_bytes(&self) -> Result<Vec<u8>>;
+}
+
+/// A property held in memory, for example read back from a file.
+pub struct MemoryProperty {
+    root: Node,
+}
+
+impl MemoryProperty {
+    /// `root` is the `call` node.
+    pub fn new(root: Node) -> Self {
+        Self { root }
+    }
+
+    fn call_node(&self) -> Option<&Node> {
+        ["game", "cardmng"]
+            .iter()
+            .find_map(|name| self.root.children().iter().find(|node| node.key() == *name))
+    }
+}
+
+impl PropertyApi for MemoryProperty {
+    fn call_name(&self) -> Option<String> {
+        self.call_node().map(|node| node.key().to_string())
+    }
+
+    fn call_attribute(&self, name: &str) -> Option<String> {
+        self.call_node()?.attr(name).map(str::to_string)
+    }
+
+    fn to_json(&self) -> Result<String> {
+        let mut object = Map::new();
+        object.insert(self.root.key().to_string(), node_to_json(&self.root));
+
+        Ok(serde_json::Value::Object(object).to_string())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        kbinxml::to_binary(&self.root)
+            .map_err(|err| anyhow::anyhow!("Could not write property as kbin: {err}"))
+    }
+}
+
+/// Children sharing a name become an array, like avs2 does.
+fn node_to_json(node: &Node) -> serde_json::Value {
+    if let Some(value) = node.value() {
+        return value_to_json(value);
+    }
+
+    let mut object = Map::new();
+    for child in node.children() {
+        if object.contains_key(child.key()) {
+            continue;
+        }
+
+        let mut values = node
+            .children()
+            .iter()
+            .filter(|sibling| sibling.key() == child.key())
+            .map(node_to_json)
+            .collect::<Vec<_>>();
+        let value = if values.len() == 1 {
+            values.remove(0)
+        } else {
+            serde_json::Value::Array(values)
+        };
+        object.insert(child.key().to_string(), value);
+    }
+
+    serde_json::Value::Object(object)
+}
+
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::S8(value) => (*value).into(),
+        Value::U8(value) => (*value).into(),
+        Value::S16(value) => (*value).into(),
+        Value::U16(value) => (*value).into(),
+        Value::S32(value) => (*value).into(),
+        Value::U32(value) => (*value).into(),
+        Value::S64(value) => (*value).into(),
+        Value::U64(value) => (*value).into(),
+        Value::Float(value) => (*value).into(),
+        Value::Double(value) => (*value).into(),
+        Value::Boolean(value) => (*value).into(),
+        Value::String(value) => value.clone().into(),
+        Value::Array(values) => match values {
+            ValueArray::S8(values) => values.clone().into(),
+            ValueArray::U8(values) => values.clone().into(),
+            ValueArray::S16(values) => values.clone().into(),
+            ValueArray::U16(values) => values.clone().into(),
+            ValueArray::S32(values) => values.clone().into(),
+            ValueArray::U32(values) => values.clone().into(),
+            ValueArray::S64(values) => values.clone().into(),
+            ValueArray::U64(values) => values.clone().into(),
+            ValueArray::Float(values) => values.clone().into(),
+            ValueArray::Double(values) => values.clone().into(),
+            ValueArray::Boolean(values) => values.clone().into(),
+            values => values.to_string().into(),
+        },
+        value => value.to_string().into(),
+    }
+}
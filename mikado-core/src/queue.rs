@@ -0,0 +1,446 @@
+use crate::helpers;
+use crate::types::tachi::{Import, ImportDocument, TachiError};
+use crate::types::user::User;
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Imports that were not confirmed by Tachi yet, one JSON object per line.
+/// Lives next to `mikado.toml` so it survives game restarts.
+const JOURNAL_PATH: &str = "mikado.queue.jsonl";
+/// Imports that Tachi refused and that would be refused again if retried as is, along with the
+/// journal lines that could not be read back. API key problems are not refusals, those imports
+/// stay in the journal until the key is fixed.
+const REJECTED_PATH: &str = "mikado.rejected.jsonl";
+
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingImport {
+    pub id: u64,
+    pub card_id: String,
+    /// Name of the profile target this import goes to, the primary one if unset.
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub attempts: u32,
+    pub import: Import,
+}
+
+impl PendingImport {
+    fn recipient(&self) -> String {
+        match &self.target {
+            Some(target) => format!("card {} on target \"{target}\"", self.card_id),
+            None => format!("card {}", self.card_id),
+        }
+    }
+
+    fn is_class_update(&self) -> bool {
+        self.import.scores.is_empty()
+    }
+
+    fn action(&self) -> &'static str {
+        if self.is_class_update() {
+            "update class"
+        } else {
+            "import score(s)"
+        }
+    }
+
+    fn outcome(&self) -> &'static str {
+        if self.is_class_update() {
+            "updated class"
+        } else {
+            "imported score(s)"
+        }
+    }
+}
+
+static SENDER: Mutex<Option<Sender<PendingImport>>> = Mutex::new(None);
+/// Held while an import is journaled and handed over, and while the worker rewrites the journal,
+/// so a rewrite never drops an import the worker has not received yet.
+static JOURNAL: Mutex<()> = Mutex::new(());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn lock_sender() -> MutexGuard<'static, Option<Sender<PendingImport>>> {
+    SENDER.lock().unwrap_or_else(|err| {
+        error!("Submission queue Mutex is poisoned: {err:#}");
+        err.into_inner()
+    })
+}
+
+fn lock_journal() -> MutexGuard<'static, ()> {
+    JOURNAL.lock().unwrap_or_else(|err| {
+        error!("Journal Mutex is poisoned: {err:#}");
+        err.into_inner()
+    })
+}
+
+/// Loads the imports left over by a previous session and starts the submission worker.
+pub fn start() -> Result<()> {
+    let mut sender = lock_sender();
+    if sender.is_some() {
+        return Ok(());
+    }
+
+    let leftovers = load_journal()?;
+    if !leftovers.is_empty() {
+        info!(
+            "Found {} pending import(s) from a previous session, they will be submitted again",
+            leftovers.len()
+        );
+    }
+    for pending in &leftovers {
+        NEXT_ID.fetch_max(pending.id + 1, Ordering::SeqCst);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("mikado-submit".to_string())
+        .spawn(move || Worker::new(leftovers, rx).run())
+        .map_err(|err| anyhow::anyhow!("Could not start submission worker: {:#}", err))?;
+    *sender = Some(tx);
+
+    Ok(())
+}
+
+/// Writes the import to the journal and hands it over to the submission worker, once for each
+/// target of the user's profile.
+///
+/// This never touches the network. If the worker is not running, the import stays in the journal
+/// and will be sent at next startup.
+pub fn submit(user: &User, import: Import) -> Result<()> {
+    let sender = lock_sender();
+    let _journal = lock_journal();
+
+    for target in &user.profile.targets {
+        let pending = PendingImport {
+            id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+            card_id: user.card_id.clone(),
+            target: Some(target.name.clone()),
+            attempts: 0,
+            import: import.clone(),
+        };
+        append(JOURNAL_PATH, &serde_json::to_vec(&pending)?)
+            .map_err(|err| anyhow::anyhow!("Could not write pending import to disk: {err:#}"))?;
+
+        let unsent = match sender.as_ref() {
+            Some(sender) => match sender.send(pending) {
+                Ok(()) => continue,
+                // The worker has stopped, the import is handed back
+                Err(mpsc::SendError(pending)) => pending,
+            },
+            None => pending,
+        };
+        warn!(
+            "Submission worker is not running, import for {} will be sent at next startup",
+            unsent.recipient()
+        );
+    }
+
+    Ok(())
+}
+
+/// Stops handing imports over to the worker, the ones submitted afterwards are only journaled.
+///
+/// Nothing is waited for: this runs from `DllMain` once the game is exiting, when the worker has
+/// already been terminated. Every import is journaled by [`submit`] before the worker sees it, so
+/// whatever was not sent yet will be at next startup.
+pub fn stop() {
+    lock_sender().take();
+}
+
+struct Entry {
+    pending: PendingImport,
+    next_attempt: Instant,
+}
+
+struct Worker {
+    entries: Vec<Entry>,
+    receiver: Receiver<PendingImport>,
+}
+
+impl Worker {
+    fn new(leftovers: Vec<PendingImport>, receiver: Receiver<PendingImport>) -> Self {
+        let now = Instant::now();
+        Self {
+            entries: leftovers
+                .into_iter()
+                .map(|pending| Entry {
+                    pending,
+                    next_attempt: now,
+                })
+                .collect(),
+            receiver,
+        }
+    }
+
+    fn run(mut self) {
+        loop {
+            self.process_due();
+
+            let message = match self.entries.iter().map(|entry| entry.next_attempt).min() {
+                Some(next_attempt) => self
+                    .receiver
+                    .recv_timeout(next_attempt.saturating_duration_since(Instant::now())),
+                None => self
+                    .receiver
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match message {
+                Ok(pending) => self.push(pending),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    /// Adopts an import [`submit`] already journaled.
+    fn push(&mut self, pending: PendingImport) {
+        debug!(
+            "Queued import #{} for {} ({} score(s))",
+            pending.id,
+            pending.recipient(),
+            pending.import.scores.len()
+        );
+        self.entries.push(Entry {
+            pending,
+            next_attempt: Instant::now(),
+        });
+    }
+
+    fn process_due(&mut self) {
+        let now = Instant::now();
+        let mut changed = false;
+
+        let mut index = 0;
+        while index < self.entries.len() {
+            let entry = &mut self.entries[index];
+            if entry.next_attempt > now {
+                index += 1;
+                continue;
+            }
+
+            changed = true;
+            match send(&entry.pending) {
+                Ok(()) => {
+                    info!(
+                        "Successfully {} for {}",
+                        entry.pending.outcome(),
+                        entry.pending.recipient()
+                    );
+                    self.entries.remove(index);
+                }
+                Err(err) => {
+                    let tachi_error = err.downcast_ref::<TachiError>();
+                    if err.is::<Unroutable>() || tachi_error.is_some_and(|err| !err.is_retryable())
+                    {
+                        error!(
+                            "Could not {} for {}, it was moved to {REJECTED_PATH}: {err:#}",
+                            entry.pending.action(),
+                            entry.pending.recipient()
+                        );
+                        let entry = self.entries.remove(index);
+                        if let Err(err) = append_rejected(&entry.pending) {
+                            error!("Could not write rejected import to disk: {err:#}");
+                        }
+                        continue;
+                    }
+
+                    entry.pending.attempts += 1;
+                    let delay = match tachi_error {
+                        Some(err) if err.needs_fixing() => MAX_RETRY_DELAY,
+                        _ => tachi_error
+                            .and_then(TachiError::retry_after)
+                            .unwrap_or_else(|| retry_delay(entry.pending.attempts)),
+                    };
+                    entry.next_attempt = Instant::now() + delay;
+                    warn!(
+                        "Could not {} for {}, retrying in {}s: {err:#}",
+                        entry.pending.action(),
+                        entry.pending.recipient(),
+                        delay.as_secs()
+                    );
+                    index += 1;
+                }
+            }
+        }
+
+        if changed {
+            self.save();
+        }
+    }
+
+    fn save(&mut self) {
+        let _journal = lock_journal();
+        // Imports journaled since the last message would be lost by the rewrite otherwise
+        while let Ok(pending) = self.receiver.try_recv() {
+            self.push(pending);
+        }
+
+        if let Err(err) = save_journal(&self.entries) {
+            error!("Could not write pending imports to disk: {err:#}");
+        }
+    }
+}
+
+/// The profile or target an import was queued for is no longer in the configuration.
+#[derive(Debug)]
+struct Unroutable(String);
+
+impl Display for Unroutable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unroutable {}
+
+fn send(pending: &PendingImport) -> Result<()> {
+    let profile = helpers::get_profile(&pending.card_id)
+        .ok_or_else(|| Unroutable(format!("No profile for card {} anymore", pending.card_id)))?;
+    let target = match &pending.target {
+        Some(name) => profile.target(name).ok_or_else(|| {
+            Unroutable(format!(
+                "Profile \"{}\" has no target named \"{name}\" anymore",
+                profile.name
+            ))
+        })?,
+        None => profile.primary(),
+    };
+
+    let document = helpers::import_to_tachi(&target.urls.import, &target.api_key, &pending.import)?;
+    if let Some(document) = document {
+        report(&pending.recipient(), &document);
+    }
+
+    Ok(())
+}
+
+/// Logs what Tachi did with an import.
+pub fn report(recipient: &str, document: &ImportDocument) {
+    debug!(
+        "Tachi import {} created {} score(s) for {recipient}",
+        document.import_id,
+        document.score_ids.len()
+    );
+
+    for error in &document.errors {
+        info!(
+            "Tachi could not import a score for {recipient}: {} ({})",
+            error.message, error.kind
+        );
+    }
+
+    for delta in &document.class_deltas {
+        info!(
+            "{} class changed from {} to {} for {recipient}",
+            delta.set,
+            delta.old.as_deref().unwrap_or("nothing"),
+            delta.new
+        );
+    }
+
+    for goal in &document.goal_info {
+        if goal.new.achieved && !goal.old.achieved {
+            info!(
+                "Goal {} achieved for {recipient} ({}/{})",
+                goal.goal_id, goal.new.progress_human, goal.new.out_of_human
+            );
+        }
+    }
+
+    for quest in &document.quest_info {
+        if quest.new.achieved && !quest.old.achieved {
+            info!("Quest {} achieved for {recipient}", quest.quest_id);
+        }
+    }
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    BASE_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+fn load_journal() -> Result<Vec<PendingImport>> {
+    if !Path::new(JOURNAL_PATH).exists() {
+        return Ok(vec![]);
+    }
+
+    let file = File::open(JOURNAL_PATH)
+        .map_err(|err| anyhow::anyhow!("Could not open pending imports file: {}", err))?;
+
+    let mut pending = vec![];
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<PendingImport>(&line) {
+            Ok(entry) => pending.push(entry),
+            Err(err) => {
+                // Moved aside before the journal is rewritten without it
+                append(REJECTED_PATH, line.as_bytes()).map_err(|write_err| {
+                    anyhow::anyhow!(
+                        "Could not move malformed pending import on line {} of {JOURNAL_PATH} to {REJECTED_PATH}: {write_err:#}",
+                        index + 1
+                    )
+                })?;
+                warn!(
+                    "Moved malformed pending import on line {} of {JOURNAL_PATH} to {REJECTED_PATH}: {err:#}",
+                    index + 1
+                );
+            }
+        }
+    }
+
+    Ok(pending)
+}
+
+fn save_journal(entries: &[Entry]) -> Result<()> {
+    if entries.is_empty() {
+        if Path::new(JOURNAL_PATH).exists() {
+            std::fs::remove_file(JOURNAL_PATH)?;
+        }
+        return Ok(());
+    }
+
+    // Write to a temporary file first so a crash mid-write never truncates the journal
+    let temporary_path = format!("{JOURNAL_PATH}.tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        for entry in entries {
+            serde_json::to_writer(&mut writer, &entry.pending)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+    }
+    std::fs::rename(&temporary_path, JOURNAL_PATH)?;
+
+    Ok(())
+}
+
+fn append_rejected(pending: &PendingImport) -> Result<()> {
+    append(REJECTED_PATH, &serde_json::to_vec(pending)?)
+}
+
+fn append(path: &str, line: &[u8]) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = line.to_vec();
+    line.push(b'\n');
+    file.write_all(&line)?;
+
+    Ok(())
+}
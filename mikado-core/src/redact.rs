@@ -0,0 +1,37 @@
+use std::sync::RwLock;
+
+/// Values that must never be written out, like API keys.
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+const REDACTED: &str = "[REDACTED]";
+
+/// Hides `secret` from every log line from now on.
+pub fn redact(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+
+    let mut secrets = SECRETS.write().unwrap_or_else(|err| err.into_inner());
+    if !secrets.iter().any(|existing| existing == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// Also catches Authorization headers, whose token may not be a configured key.
+pub fn redacted(message: String) -> String {
+    let secrets = SECRETS.read().unwrap_or_else(|err| err.into_inner());
+    let mut message = secrets.iter().fold(message, |message, secret| {
+        message.replace(secret.as_str(), REDACTED)
+    });
+
+    let mut start = 0;
+    while let Some(index) = message[start..].find("Bearer ") {
+        let token_start = start + index + "Bearer ".len();
+        let token_end = message[token_start..]
+            .find(|c: char| c.is_whitespace() || c == '"' || c == '\'')
+            .map_or(message.len(), |end| token_start + end);
+        message.replace_range(token_start..token_end, REDACTED);
+        start = token_start + REDACTED.len();
+    }
+
+    message
+}
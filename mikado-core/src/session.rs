@@ -0,0 +1,292 @@
+use crate::pbs::{self, Improvement};
+use crate::types::GameVersion;
+use crate::types::game::Track;
+use crate::types::tachi::{SkillLevel, TachiLamp};
+use crate::types::user::User;
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use log::{error, info};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Where a summary of each session is written when it ends.
+const SESSION_DIRECTORY: &str = "mikado-sessions";
+
+/// Session of the player whose card was scanned last.
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+struct Session {
+    card_id: String,
+    profile: String,
+    started: DateTime<Local>,
+    ended: Option<DateTime<Local>>,
+    songs: u32,
+    /// From worst to best.
+    lamps: Vec<LampCount>,
+    /// `None` if the PBs were never loaded from Tachi, every play would look like a new PB.
+    new_pbs: Option<Vec<Improvement>>,
+    /// Class at the end of the card's last session that sent one.
+    previous_class: Option<SkillLevel>,
+    class: Option<SkillLevel>,
+    critical: u32,
+    near: u32,
+    error: u32,
+    fast: u32,
+    slow: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LampCount {
+    lamp: TachiLamp,
+    count: u32,
+}
+
+/// Starts the session of a player who scanned their card, ending the previous one.
+pub fn start(user: &User) {
+    end();
+
+    let session = Session {
+        card_id: user.card_id.clone(),
+        profile: user.profile.name.clone(),
+        started: user.session_started,
+        ended: None,
+        songs: 0,
+        lamps: vec![],
+        new_pbs: None,
+        previous_class: last_class(&user.card_id),
+        class: None,
+        critical: 0,
+        near: 0,
+        error: 0,
+        fast: 0,
+        slow: 0,
+    };
+    match SESSION.lock() {
+        Ok(mut current) => *current = Some(session),
+        Err(err) => error!("Session Mutex is poisoned: {err:#}"),
+    }
+}
+
+/// Adds the tracks of a `save_m` and the PBs they improved to the session of the card.
+pub fn record_tracks(
+    card_id: &str,
+    version: GameVersion,
+    tracks: &[Track],
+    improvements: &[Improvement],
+) {
+    with_session(card_id, |session| {
+        for track in tracks {
+            session.songs += 1;
+            session.critical += track.critical;
+            session.near += track.near;
+            session.error += track.error;
+            session.fast += track.judge[0];
+            session.slow += track.judge[6];
+
+            let lamp = TachiLamp::from_clear_type(version, track.clear_type);
+            match session.lamps.iter_mut().find(|count| count.lamp == lamp) {
+                Some(count) => count.count += 1,
+                None => {
+                    session.lamps.push(LampCount { lamp, count: 1 });
+                    session.lamps.sort_by_key(|count| count.lamp.rank());
+                }
+            }
+        }
+
+        if pbs::is_seeded(card_id) {
+            session
+                .new_pbs
+                .get_or_insert_with(Vec::new)
+                .extend_from_slice(improvements);
+        }
+    });
+}
+
+/// Records the class sent with the final `save` of the session.
+pub fn record_class(card_id: &str, class: SkillLevel) {
+    with_session(card_id, |session| session.class = Some(class));
+}
+
+/// Ends the current session, if any, and writes its summary to the console and to a file.
+pub fn end() {
+    let session = match SESSION.lock() {
+        Ok(mut current) => current.take(),
+        Err(err) => {
+            error!("Session Mutex is poisoned: {err:#}");
+            return;
+        }
+    };
+    let Some(mut session) = session else {
+        return;
+    };
+    session.ended = Some(Local::now());
+
+    summarize(&session);
+    if let Err(err) = write(&session) {
+        error!("{err:#}");
+    }
+}
+
+fn with_session(card_id: &str, f: impl FnOnce(&mut Session)) {
+    match SESSION.lock() {
+        Ok(mut current) => {
+            if let Some(session) = current
+                .as_mut()
+                .filter(|session| session.card_id == card_id)
+            {
+                f(session);
+            }
+        }
+        Err(err) => error!("Session Mutex is poisoned: {err:#}"),
+    }
+}
+
+fn summarize(session: &Session) {
+    let minutes = session
+        .ended
+        .map(|ended| (ended - session.started).num_minutes())
+        .unwrap_or_default();
+    info!(
+        "Session of profile \"{}\" is over after {minutes} minute(s), {} song(s) played",
+        session.profile, session.songs
+    );
+    if session.songs == 0 {
+        return;
+    }
+
+    let lamps = session
+        .lamps
+        .iter()
+        .rev()
+        .map(|count| format!("{} {}", count.count, count.lamp.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    info!("Lamps: {lamps}");
+    match &session.new_pbs {
+        Some(new_pbs) => info!("New Tachi PBs: {}", new_pbs.len()),
+        None => info!("New Tachi PBs: unknown, PBs were not loaded from Tachi"),
+    }
+    match (session.previous_class, session.class) {
+        (Some(previous), Some(class)) if previous != class => {
+            info!("Class: {} → {}", previous.name(), class.name())
+        }
+        (_, Some(class)) => info!("Class: {}", class.name()),
+        (_, None) => {}
+    }
+    info!(
+        "Judgements: {} critical, {} near, {} error",
+        session.critical, session.near, session.error
+    );
+    let timed = session.fast + session.slow;
+    if timed > 0 {
+        info!(
+            "Fast/slow: {}/{} ({:.1}% fast)",
+            session.fast,
+            session.slow,
+            session.fast as f32 * 100.0 / timed as f32
+        );
+    }
+}
+
+/// Reads the class back from the summaries of the previous sessions of the card.
+fn last_class(card_id: &str) -> Option<SkillLevel> {
+    let prefix = format!("{card_id}-");
+    let mut paths = std::fs::read_dir(SESSION_DIRECTORY)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with(&prefix))
+        })
+        .collect::<Vec<_>>();
+    // Names end with the start of the session
+    paths.sort();
+
+    paths.iter().rev().find_map(|path| {
+        let content = std::fs::read(path).ok()?;
+        let session = serde_json::from_slice::<serde_json::Value>(&content).ok()?;
+        serde_json::from_value(session["class"].clone()).ok()
+    })
+}
+
+fn write(session: &Session) -> Result<()> {
+    std::fs::create_dir_all(SESSION_DIRECTORY)
+        .map_err(|err| anyhow::anyhow!("Could not create {SESSION_DIRECTORY} folder: {}", err))?;
+
+    let path = Path::new(SESSION_DIRECTORY).join(format!(
+        "{}-{}.json",
+        session.card_id,
+        session.started.format("%Y%m%d-%H%M%S")
+    ));
+    let json = serde_json::to_vec_pretty(session)?;
+    std::fs::write(&path, json)
+        .map_err(|err| anyhow::anyhow!("Could not write {}: {}", path.display(), err))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use crate::types::user::{Profile, TachiUrls, Target};
+
+    fn user(started: DateTime<Local>) -> User {
+        let target = Target {
+            name: "default".to_string(),
+            api_key: "test-key".to_string(),
+            urls: TachiUrls::default(),
+        };
+        User {
+            tachi_id: 42,
+            card_id: testing::CARD_ID.to_string(),
+            profile: Profile::new("test".to_string(), vec![target], None).unwrap(),
+            session_started: started,
+        }
+    }
+
+    fn written() -> Vec<serde_json::Value> {
+        let mut paths = std::fs::read_dir(SESSION_DIRECTORY)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn class_change_is_read_from_previous_session() {
+        let _guard = testing::isolate();
+        let started = Local::now() - chrono::Duration::days(1);
+
+        start(&user(started));
+        record_class(testing::CARD_ID, SkillLevel::from(5));
+        end();
+
+        start(&user(started + chrono::Duration::hours(1)));
+        end();
+
+        start(&user(Local::now()));
+        record_class(testing::CARD_ID, SkillLevel::from(6));
+        end();
+
+        let sessions = written();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(sessions[0]["previous_class"], serde_json::Value::Null);
+        assert_eq!(sessions[0]["class"], "DAN_5");
+        // A session without a save keeps the class of the one before
+        assert_eq!(sessions[1]["previous_class"], "DAN_5");
+        assert_eq!(sessions[1]["class"], serde_json::Value::Null);
+        assert_eq!(sessions[2]["previous_class"], "DAN_5");
+        assert_eq!(sessions[2]["class"], "DAN_6");
+    }
+}
@@ -0,0 +1,241 @@
+//! Helpers shared by the tests that go through the hook, the queue or Tachi.
+
+use crate::configuration::{
+    ActiveConfiguration, Configuration, GeneralConfiguration, ProfileConfiguration,
+    TachiConfiguration,
+};
+use kbinxml::{Node, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Internal ID of the card the test profile is bound to.
+pub const CARD_ID: &str = "E004010027A5FC68";
+
+/// The working directory and the hook state are global, tests using them must hold this.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Keeps the global state locked and removes the working directory when dropped.
+pub struct Isolation {
+    _directory: TempDir,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Drop for Isolation {
+    fn drop(&mut self) {
+        // The directory can't be removed on Windows while it is the working directory
+        let _ = std::env::set_current_dir(std::env::temp_dir());
+    }
+}
+
+/// Locks the global state and moves to an empty working directory, where the journal, history
+/// and session files are written.
+pub fn isolate() -> Isolation {
+    let guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+    let directory = tempfile::Builder::new()
+        .prefix("mikado-test-")
+        .tempdir()
+        .unwrap();
+    std::env::set_current_dir(directory.path()).unwrap();
+
+    Isolation {
+        _directory: directory,
+        _guard: guard,
+    }
+}
+
+pub fn working_file(name: &str) -> PathBuf {
+    std::env::current_dir().unwrap().join(name)
+}
+
+/// A profile bound to [`CARD_ID`] that sends everything to `base_url`.
+pub fn configure(base_url: &str, general: GeneralConfiguration) {
+    let profile = ProfileConfiguration {
+        cards: vec![CARD_ID.to_string()],
+        api_key: Some("test-key".to_string()),
+        ..Default::default()
+    };
+    let configuration = Configuration {
+        general: GeneralConfiguration {
+            enable: true,
+            timeout: 5000,
+            ..general
+        },
+        cards: None,
+        profiles: [("test".to_string(), profile)].into(),
+        tachi: TachiConfiguration {
+            base_url: base_url.to_string(),
+            status: "/status".to_string(),
+            import: "/import".to_string(),
+            pbs: "/pbs/{}".to_string(),
+            rivals: "/rivals/{}".to_string(),
+            api_key: None,
+        },
+    };
+
+    crate::set_configuration(ActiveConfiguration::new(configuration).unwrap());
+}
+
+/// `cardmng inquire` for [`CARD_ID`].
+pub fn inquire() -> Node {
+    let mut cardmng = Node::with_nodes("cardmng", vec![]);
+    cardmng.set_attr("method", "inquire");
+    cardmng.set_attr("cardid", CARD_ID);
+
+    Node::with_nodes("call", vec![cardmng])
+}
+
+/// A `game` call with no content, like `sv6_load_m`.
+pub fn game_call(method: &str, children: Vec<Node>) -> Node {
+    let mut game = Node::with_nodes("game", children);
+    game.set_attr("method", method);
+
+    Node::with_nodes("call", vec![game])
+}
+
+/// `sv6_save_m` of a single track, sent by a player with a card.
+pub fn save_m(music_id: u32, score: u32) -> Node {
+    let track = Node::with_nodes(
+        "track",
+        [
+            ("music_id", music_id),
+            ("music_type", 3),
+            ("score", score),
+            ("exscore", 0),
+            ("clear_type", 2),
+            ("max_chain", 1000),
+            ("critical", 900),
+            ("near", 90),
+            ("error", 10),
+            ("effective_rate", 7500),
+            ("gauge_type", 0),
+        ]
+        .into_iter()
+        .map(|(key, value)| Node::with_value(key, Value::U32(value)))
+        .chain([Node::with_value(
+            "judge",
+            Value::Array(kbinxml::ValueArray::U32(vec![40, 0, 0, 0, 0, 0, 50])),
+        )])
+        .collect(),
+    );
+
+    game_call(
+        "sv6_save_m",
+        vec![
+            Node::with_value("refid", Value::String("REFID".to_string())),
+            track,
+        ],
+    )
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+/// Answers Tachi requests on a local port and records them as soon as they arrive.
+pub struct MockTachi {
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockTachi {
+    /// `respond` returns how long to stall the response for and its JSON body.
+    pub fn start(respond: impl Fn(&Request) -> (Duration, String) + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let respond = Arc::new(respond);
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let respond = respond.clone();
+                let recorded = recorded.clone();
+                std::thread::spawn(move || {
+                    let Some(request) = read_request(&mut stream) else {
+                        return;
+                    };
+                    recorded.lock().unwrap().push(request.clone());
+
+                    let (delay, body) = respond(&request);
+                    std::thread::sleep(delay);
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                });
+            }
+        });
+
+        Self { base_url, requests }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The first request to `path`, waiting up to `timeout` for it.
+    pub fn wait_for(&self, path: &str, timeout: Duration) -> Option<Request> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let found = self
+                .requests()
+                .into_iter()
+                .find(|request| request.path == path);
+            if found.is_some() || Instant::now() > deadline {
+                return found;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+fn read_request(stream: &mut std::net::TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            length = value.trim().parse().ok()?;
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+/// Successful Tachi response wrapping `body`.
+pub fn tachi_response(body: serde_json::Value) -> String {
+    serde_json::json!({ "success": true, "description": "", "body": body }).to_string()
+}
@@ -0,0 +1,87 @@
+use either::Either;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Property {
+    pub call: CallStruct,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallStruct {
+    pub game: Game,
+}
+
+/// Told apart by their mandatory fields, `save_c` has to come before `save` as it is stricter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Game {
+    Scores(GameScores),
+    Course(GameCourse),
+    Save(GameSave),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameScores {
+    #[serde(rename = "refid", default)]
+    pub ref_id: Option<String>,
+    #[serde(with = "either::serde_untagged", rename = "track")]
+    pub tracks: Either<Track, Vec<Track>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Track {
+    pub music_id: u32,
+    pub music_type: u32,
+    pub score: u32,
+    #[serde(rename = "exscore")]
+    pub ex_score: u32,
+    pub clear_type: u32,
+    pub max_chain: u32,
+    pub critical: u32,
+    pub near: u32,
+    pub error: u32,
+    pub effective_rate: u32,
+    pub gauge_type: u32,
+    pub judge: [u32; 7],
+    /// Milliseconds since the Unix epoch. The game does not send it, so it is set to the time
+    /// the hook first saw the property, minus the stage offset within a skill analyzer run, and
+    /// kept from then on, even if the score is retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_achieved: Option<u128>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameSave {
+    #[serde(rename = "refid", default)]
+    pub ref_id: Option<String>,
+    pub skill_level: u32,
+}
+
+/// Skill analyzer result, sent with `save_c` once the course is over.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameCourse {
+    #[serde(rename = "refid", default)]
+    pub ref_id: Option<String>,
+    #[serde(rename = "ssnid")]
+    pub season_id: u32,
+    #[serde(rename = "crsid")]
+    pub course_id: u32,
+    #[serde(rename = "sc", default)]
+    pub score: u32,
+    #[serde(rename = "ex", default)]
+    pub ex_score: u32,
+    #[serde(rename = "ct", default)]
+    pub clear_type: u32,
+    #[serde(rename = "gr", default)]
+    pub grade: u32,
+    /// Gauge left at the end of the course, in hundredths of a percent.
+    #[serde(rename = "ar", default)]
+    pub achievement_rate: u32,
+}
+
+impl GameCourse {
+    /// 0 is an unplayed course and 1 a failed one, anything above is a pass.
+    pub fn is_passed(&self) -> bool {
+        self.clear_type >= 2
+    }
+}
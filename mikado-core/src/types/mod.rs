@@ -1,4 +1,3 @@
-use crate::helpers;
 use std::fmt::{Display, Formatter};
 
 pub mod cloudlink;
@@ -28,6 +27,25 @@ impl GameVersion {
         }
     }
 
+    pub fn from_tachi_id(id: &str) -> Option<Self> {
+        match id {
+            "exceed" => Some(GameVersion::ExceedGear),
+            "nabla" => Some(GameVersion::Nabla),
+            _ => None,
+        }
+    }
+
+    /// Version sending `method`, like `sv6_save_m`.
+    pub fn from_method(method: &str) -> Option<Self> {
+        [GameVersion::ExceedGear, GameVersion::Nabla]
+            .into_iter()
+            .find(|version| {
+                method
+                    .strip_prefix(version.method_prefix())
+                    .is_some_and(|rest| rest.starts_with('_'))
+            })
+    }
+
     pub fn method_prefix(self) -> &'static str {
         match self {
             GameVersion::ExceedGear => "sv6",
@@ -58,18 +76,17 @@ pub enum NotSupportedReason<'a> {
 
 #[allow(unused)]
 impl GameProperties {
-    pub unsafe fn from_ea3_node(node: *const ()) -> Option<GameProperties> {
-        let model =
-            unsafe { helpers::read_node_str(node, c"/soft/model".as_ptr(), 3) }?.into_boxed_str();
-        let dest =
-            unsafe { helpers::read_node_str(node, c"/soft/dest".as_ptr(), 1) }?.into_boxed_str();
-        let spec =
-            unsafe { helpers::read_node_str(node, c"/soft/spec".as_ptr(), 1) }?.into_boxed_str();
-        let revision =
-            unsafe { helpers::read_node_str(node, c"/soft/rev".as_ptr(), 1) }?.into_boxed_str();
-        let ext = unsafe { helpers::read_node_str(node, c"/soft/ext".as_ptr(), 10) }?
-            .parse()
-            .unwrap_or(0);
+    pub fn new(
+        model: impl Into<Box<str>>,
+        dest: impl Into<Box<str>>,
+        spec: impl Into<Box<str>>,
+        revision: impl Into<Box<str>>,
+        ext: u64,
+    ) -> GameProperties {
+        let model = model.into();
+        let dest = dest.into();
+        let spec = spec.into();
+        let revision = revision.into();
 
         let valkyrie = spec.as_ref() == "G" || spec.as_ref() == "H";
         let maxxive_support = ext >= 2025042200;
@@ -80,7 +97,7 @@ impl GameProperties {
             GameVersion::ExceedGear
         };
 
-        Some(GameProperties {
+        GameProperties {
             model,
             dest,
             spec,
@@ -90,7 +107,7 @@ impl GameProperties {
             maxxive_support,
             ultimate_support,
             version,
-        })
+        }
     }
 
     pub fn model(&self) -> &str {
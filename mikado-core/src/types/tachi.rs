@@ -0,0 +1,723 @@
+use super::GameVersion;
+use super::game::Track;
+use anyhow::Result;
+use log::warn;
+use num_enum::{FromPrimitive, IntoPrimitive};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Import {
+    pub meta: ImportMeta,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classes: Option<ImportClasses>,
+    pub scores: Vec<ImportScore>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportMeta {
+    pub game: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub playtype: Option<String>,
+    pub service: String,
+    pub version: String,
+}
+
+impl ImportMeta {
+    pub fn new(version: GameVersion) -> Self {
+        Self {
+            game: "sdvx".to_string(),
+            playtype: None,
+            service: "Mikado".to_string(),
+            version: version.tachi_id().to_string(),
+        }
+    }
+
+    /// BATCH-MANUAL files are uploaded by hand and must state the playtype.
+    pub fn batch_manual(version: GameVersion) -> Self {
+        Self {
+            playtype: Some("Single".to_string()),
+            ..Self::new(version)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportClasses {
+    pub dan: SkillLevel,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, FromPrimitive, Serialize, Deserialize)]
+#[repr(u32)]
+pub enum SkillLevel {
+    #[num_enum(default)]
+    #[serde(rename = "DAN_1")]
+    First = 1,
+    #[serde(rename = "DAN_2")]
+    Second = 2,
+    #[serde(rename = "DAN_3")]
+    Third = 3,
+    #[serde(rename = "DAN_4")]
+    Fourth = 4,
+    #[serde(rename = "DAN_5")]
+    Fifth = 5,
+    #[serde(rename = "DAN_6")]
+    Sixth = 6,
+    #[serde(rename = "DAN_7")]
+    Seventh = 7,
+    #[serde(rename = "DAN_8")]
+    Eighth = 8,
+    #[serde(rename = "DAN_9")]
+    Ninth = 9,
+    #[serde(rename = "DAN_10")]
+    Tenth = 10,
+    #[serde(rename = "DAN_11")]
+    Eleventh = 11,
+    #[serde(rename = "INF")]
+    Infinite = 12,
+}
+
+impl SkillLevel {
+    /// Name used by Tachi.
+    pub fn name(self) -> &'static str {
+        match self {
+            SkillLevel::First => "DAN_1",
+            SkillLevel::Second => "DAN_2",
+            SkillLevel::Third => "DAN_3",
+            SkillLevel::Fourth => "DAN_4",
+            SkillLevel::Fifth => "DAN_5",
+            SkillLevel::Sixth => "DAN_6",
+            SkillLevel::Seventh => "DAN_7",
+            SkillLevel::Eighth => "DAN_8",
+            SkillLevel::Ninth => "DAN_9",
+            SkillLevel::Tenth => "DAN_10",
+            SkillLevel::Eleventh => "DAN_11",
+            SkillLevel::Infinite => "INF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportScore {
+    pub score: u32,
+    pub lamp: TachiLamp,
+    #[serde(rename = "matchType")]
+    pub match_type: String,
+    pub identifier: String,
+    pub difficulty: TachiDifficulty,
+    #[serde(rename = "timeAchieved")]
+    pub time_achieved: u128,
+    pub judgements: Judgements,
+    #[serde(rename = "hitMeta")]
+    pub hit_meta: HitMeta,
+    #[serde(rename = "scoreMeta", skip_serializing_if = "Option::is_none")]
+    pub score_meta: Option<ScoreMeta>,
+}
+
+impl ImportScore {
+    /// `in_skill_analyzer` is left unset when it is not known, like for plays read back from the
+    /// history.
+    pub fn from_track(
+        version: GameVersion,
+        track: &Track,
+        time_achieved: u128,
+        in_skill_analyzer: Option<bool>,
+    ) -> Self {
+        Self {
+            score: track.score,
+            lamp: TachiLamp::from_clear_type(version, track.clear_type),
+            match_type: "sdvxInGameID".to_string(),
+            identifier: track.music_id.to_string(),
+            difficulty: TachiDifficulty::from(track.music_type),
+            time_achieved,
+            judgements: Judgements {
+                critical: track.critical,
+                near: track.near,
+                miss: track.error,
+            },
+            hit_meta: HitMeta {
+                fast: track.judge[0],
+                slow: track.judge[6],
+                max_combo: track.max_chain,
+                ex_score: if track.ex_score != 0 {
+                    Some(track.ex_score)
+                } else {
+                    None
+                },
+                gauge: track.effective_rate as f32 / 100.0,
+            },
+            score_meta: Some(ScoreMeta {
+                in_skill_analyzer,
+                gauge: TachiGauge::from_gauge_type(version, track.gauge_type),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScoreMeta {
+    #[serde(rename = "inSkillAnalyser", skip_serializing_if = "Option::is_none")]
+    pub in_skill_analyzer: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gauge: Option<TachiGauge>,
+}
+
+/// Gauges of Tachi's SDVX `scoreMeta`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TachiGauge {
+    Effective,
+    Excessive,
+    Permissive,
+    Blaster,
+    /// Added by Nabla
+    Maxxive,
+}
+
+impl TachiGauge {
+    /// `None` for gauges the game version is not known to have, the score is then imported
+    /// without one.
+    pub fn from_gauge_type(version: GameVersion, gauge_type: u32) -> Option<Self> {
+        let gauge = match (version, gauge_type) {
+            (_, 0) => TachiGauge::Effective,
+            (_, 1) => TachiGauge::Excessive,
+            (_, 2) => TachiGauge::Permissive,
+            (_, 3) => TachiGauge::Blaster,
+            (GameVersion::Nabla, 4) => TachiGauge::Maxxive,
+            _ => return unknown_gauge(version, gauge_type),
+        };
+
+        Some(gauge)
+    }
+}
+
+fn unknown_gauge(version: GameVersion, gauge_type: u32) -> Option<TachiGauge> {
+    warn!(
+        "Unknown {} gauge type {gauge_type}, the score is imported without its gauge",
+        version.display_name()
+    );
+    None
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum TachiLamp {
+    #[serde(rename = "FAILED")]
+    Failed,
+    #[serde(rename = "CLEAR")]
+    Clear,
+    #[serde(rename = "EXCESSIVE CLEAR")]
+    ExcessiveClear,
+    #[serde(rename = "ULTIMATE CHAIN")]
+    UltimateChain,
+    #[serde(rename = "PERFECT ULTIMATE CHAIN")]
+    PerfectUltimateChain,
+    #[serde(rename = "MAXXIVE CLEAR")]
+    MaxxiveClear,
+}
+
+impl TachiLamp {
+    pub fn from_clear_type(version: GameVersion, clear_type: u32) -> Self {
+        match version {
+            GameVersion::ExceedGear => match clear_type {
+                2 => TachiLamp::Clear,
+                3 => TachiLamp::ExcessiveClear,
+                4 => TachiLamp::UltimateChain,
+                5 => TachiLamp::PerfectUltimateChain,
+                6 => TachiLamp::MaxxiveClear,
+                _ => TachiLamp::Failed,
+            },
+            GameVersion::Nabla => match clear_type {
+                2 => TachiLamp::Clear,
+                3 => TachiLamp::ExcessiveClear,
+                4 => TachiLamp::MaxxiveClear,
+                5 => TachiLamp::UltimateChain,
+                6 => TachiLamp::PerfectUltimateChain,
+                _ => TachiLamp::Failed,
+            },
+        }
+    }
+
+    /// Name used by Tachi.
+    pub fn name(self) -> &'static str {
+        match self {
+            TachiLamp::Failed => "FAILED",
+            TachiLamp::Clear => "CLEAR",
+            TachiLamp::ExcessiveClear => "EXCESSIVE CLEAR",
+            TachiLamp::UltimateChain => "ULTIMATE CHAIN",
+            TachiLamp::PerfectUltimateChain => "PERFECT ULTIMATE CHAIN",
+            TachiLamp::MaxxiveClear => "MAXXIVE CLEAR",
+        }
+    }
+
+    /// Position from worst to best, which is not the order of the game's indexes.
+    pub fn rank(self) -> u8 {
+        match self {
+            TachiLamp::Failed => 0,
+            TachiLamp::Clear => 1,
+            TachiLamp::ExcessiveClear => 2,
+            TachiLamp::MaxxiveClear => 3,
+            TachiLamp::UltimateChain => 4,
+            TachiLamp::PerfectUltimateChain => 5,
+        }
+    }
+
+    pub fn to_index(self, version: GameVersion) -> u32 {
+        match version {
+            GameVersion::ExceedGear => match self {
+                TachiLamp::Failed => 1,
+                TachiLamp::Clear => 2,
+                TachiLamp::ExcessiveClear => 3,
+                TachiLamp::UltimateChain => 4,
+                TachiLamp::PerfectUltimateChain => 5,
+                TachiLamp::MaxxiveClear => 6,
+            },
+            GameVersion::Nabla => match self {
+                TachiLamp::Failed => 1,
+                TachiLamp::Clear => 2,
+                TachiLamp::ExcessiveClear => 3,
+                TachiLamp::MaxxiveClear => 4,
+                TachiLamp::UltimateChain => 5,
+                TachiLamp::PerfectUltimateChain => 6,
+            },
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Eq, PartialEq, Hash, FromPrimitive, IntoPrimitive, Serialize, Deserialize,
+)]
+#[repr(u32)]
+pub enum TachiDifficulty {
+    #[num_enum(default)]
+    #[serde(rename = "NOV")]
+    Novice = 0,
+    #[serde(rename = "ADV")]
+    Advanced = 1,
+    #[serde(rename = "EXH")]
+    Exhaust = 2,
+    #[serde(rename = "ANY_INF")]
+    AnyInfinite = 3,
+    #[serde(rename = "MXM")]
+    Maximum = 4,
+    #[serde(rename = "ULT")]
+    Ultimate = 5,
+}
+
+impl TachiDifficulty {
+    /// Name used by Tachi.
+    pub fn name(self) -> &'static str {
+        match self {
+            TachiDifficulty::Novice => "NOV",
+            TachiDifficulty::Advanced => "ADV",
+            TachiDifficulty::Exhaust => "EXH",
+            TachiDifficulty::AnyInfinite => "ANY_INF",
+            TachiDifficulty::Maximum => "MXM",
+            TachiDifficulty::Ultimate => "ULT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Judgements {
+    pub critical: u32,
+    pub near: u32,
+    pub miss: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HitMeta {
+    pub fast: u32,
+    pub slow: u32,
+    #[serde(rename = "maxCombo")]
+    pub max_combo: u32,
+    #[serde(rename = "exScore")]
+    pub ex_score: Option<u32>,
+    pub gauge: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TachiResponse<T> {
+    pub success: bool,
+    pub description: String,
+    pub body: Option<T>,
+}
+
+impl<T> TachiResponse<T> {
+    pub fn into_body(self) -> Result<T> {
+        if !self.success {
+            return Err(TachiError::Rejected(self.description).into());
+        }
+
+        self.body.ok_or_else(|| {
+            TachiError::MalformedBody(format!("no body in response \"{}\"", self.description))
+                .into()
+        })
+    }
+}
+
+/// The parts of a Tachi user document Mikado cares about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TachiUser {
+    pub id: u64,
+    pub username: String,
+}
+
+/// Direct-manual imports are either processed right away or deferred to Tachi's import queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ImportResponse {
+    Deferred(DeferredImport),
+    Completed(ImportDocument),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeferredImport {
+    pub url: String,
+    #[serde(rename = "importID")]
+    pub import_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "importStatus")]
+pub enum ImportStatus {
+    #[serde(rename = "ongoing")]
+    Ongoing {
+        #[serde(default)]
+        progress: Option<ImportProgress>,
+    },
+    #[serde(rename = "completed")]
+    Completed { import: ImportDocument },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub description: String,
+    #[serde(default)]
+    pub value: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportDocument {
+    #[serde(rename = "importID")]
+    pub import_id: String,
+    #[serde(rename = "scoreIDs", default)]
+    pub score_ids: Vec<String>,
+    #[serde(default)]
+    pub errors: Vec<ImportError>,
+    #[serde(rename = "classDeltas", default)]
+    pub class_deltas: Vec<ClassDelta>,
+    #[serde(rename = "goalInfo", default)]
+    pub goal_info: Vec<GoalImportInfo>,
+    #[serde(rename = "questInfo", default)]
+    pub quest_info: Vec<QuestImportInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportError {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassDelta {
+    pub set: String,
+    pub old: Option<String>,
+    pub new: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoalImportInfo {
+    #[serde(rename = "goalID")]
+    pub goal_id: String,
+    pub old: GoalImportStat,
+    pub new: GoalImportStat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GoalImportStat {
+    pub achieved: bool,
+    #[serde(rename = "progressHuman", default)]
+    pub progress_human: String,
+    #[serde(rename = "outOfHuman", default)]
+    pub out_of_human: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestImportInfo {
+    #[serde(rename = "questID")]
+    pub quest_id: String,
+    pub old: QuestImportStat,
+    pub new: QuestImportStat,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestImportStat {
+    pub achieved: bool,
+    #[serde(default)]
+    pub progress: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TachiError {
+    Unreachable(String),
+    Unauthorized(u16),
+    RateLimited(Option<Duration>),
+    Server(u16),
+    Rejected(String),
+    MalformedBody(String),
+}
+
+impl TachiError {
+    /// Whether sending the exact same request later has a chance to succeed, possibly once the
+    /// API key or Tachi itself has been fixed.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, TachiError::Rejected(_))
+    }
+
+    /// Whether the request will keep failing until someone fixes the API key or Tachi, rather
+    /// than until a passing outage is over.
+    pub fn needs_fixing(&self) -> bool {
+        matches!(
+            self,
+            TachiError::Unauthorized(_) | TachiError::MalformedBody(_)
+        )
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            TachiError::RateLimited(retry_after) => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl Display for TachiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TachiError::Unreachable(err) => write!(
+                f,
+                "Could not reach Tachi API, check your connection and the base_url in mikado.toml: {err}"
+            ),
+            TachiError::Unauthorized(401) => write!(
+                f,
+                "Tachi did not accept the API key (HTTP 401), check that it is correct and was not revoked"
+            ),
+            TachiError::Unauthorized(status) => write!(
+                f,
+                "The API key is not allowed to do this (HTTP {status}), create a new one with the submit_score permission"
+            ),
+            TachiError::RateLimited(Some(retry_after)) => write!(
+                f,
+                "Tachi is rate limiting requests (HTTP 429), retrying in {}s",
+                retry_after.as_secs()
+            ),
+            TachiError::RateLimited(None) => {
+                write!(
+                    f,
+                    "Tachi is rate limiting requests (HTTP 429), retrying later"
+                )
+            }
+            TachiError::Server(status) => write!(
+                f,
+                "Tachi is having issues (HTTP {status}), nothing to do on your side but wait"
+            ),
+            TachiError::Rejected(description) => {
+                write!(f, "Tachi refused the request: {description}")
+            }
+            TachiError::MalformedBody(err) => write!(
+                f,
+                "Could not understand Tachi response, check that base_url points to a Tachi instance: {err}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TachiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exceed_gear_gauges() {
+        let version = GameVersion::ExceedGear;
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 0),
+            Some(TachiGauge::Effective)
+        );
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 1),
+            Some(TachiGauge::Excessive)
+        );
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 2),
+            Some(TachiGauge::Permissive)
+        );
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 3),
+            Some(TachiGauge::Blaster)
+        );
+        assert_eq!(TachiGauge::from_gauge_type(version, 4), None);
+    }
+
+    #[test]
+    fn nabla_gauges() {
+        let version = GameVersion::Nabla;
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 0),
+            Some(TachiGauge::Effective)
+        );
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 3),
+            Some(TachiGauge::Blaster)
+        );
+        assert_eq!(
+            TachiGauge::from_gauge_type(version, 4),
+            Some(TachiGauge::Maxxive)
+        );
+        assert_eq!(TachiGauge::from_gauge_type(version, 5), None);
+    }
+
+    const LAMPS: [TachiLamp; 6] = [
+        TachiLamp::Failed,
+        TachiLamp::Clear,
+        TachiLamp::ExcessiveClear,
+        TachiLamp::MaxxiveClear,
+        TachiLamp::UltimateChain,
+        TachiLamp::PerfectUltimateChain,
+    ];
+
+    #[test]
+    fn exceed_gear_clear_types() {
+        let version = GameVersion::ExceedGear;
+        let expected = [
+            (1, TachiLamp::Failed),
+            (2, TachiLamp::Clear),
+            (3, TachiLamp::ExcessiveClear),
+            (4, TachiLamp::UltimateChain),
+            (5, TachiLamp::PerfectUltimateChain),
+            (6, TachiLamp::MaxxiveClear),
+        ];
+        for (clear_type, lamp) in expected {
+            assert_eq!(TachiLamp::from_clear_type(version, clear_type), lamp);
+            assert_eq!(lamp.to_index(version), clear_type);
+        }
+        assert_eq!(TachiLamp::from_clear_type(version, 0), TachiLamp::Failed);
+        assert_eq!(TachiLamp::from_clear_type(version, 7), TachiLamp::Failed);
+    }
+
+    #[test]
+    fn nabla_clear_types() {
+        let version = GameVersion::Nabla;
+        let expected = [
+            (1, TachiLamp::Failed),
+            (2, TachiLamp::Clear),
+            (3, TachiLamp::ExcessiveClear),
+            (4, TachiLamp::MaxxiveClear),
+            (5, TachiLamp::UltimateChain),
+            (6, TachiLamp::PerfectUltimateChain),
+        ];
+        for (clear_type, lamp) in expected {
+            assert_eq!(TachiLamp::from_clear_type(version, clear_type), lamp);
+            assert_eq!(lamp.to_index(version), clear_type);
+        }
+        assert_eq!(TachiLamp::from_clear_type(version, 0), TachiLamp::Failed);
+        assert_eq!(TachiLamp::from_clear_type(version, 7), TachiLamp::Failed);
+    }
+
+    #[test]
+    fn lamps_rank_from_worst_to_best() {
+        let ranks = LAMPS.map(TachiLamp::rank);
+        assert_eq!(ranks, [0, 1, 2, 3, 4, 5]);
+    }
+
+    fn track() -> Track {
+        Track {
+            music_id: 1500,
+            music_type: 4,
+            score: 9_912_345,
+            ex_score: 2345,
+            clear_type: 4,
+            max_chain: 1200,
+            critical: 1150,
+            near: 40,
+            error: 10,
+            effective_rate: 8450,
+            gauge_type: 1,
+            judge: [25, 0, 0, 0, 0, 0, 15],
+            time_achieved: None,
+        }
+    }
+
+    #[test]
+    fn import_score_from_track() {
+        let score = ImportScore::from_track(GameVersion::Nabla, &track(), 1234, Some(true));
+        let json = serde_json::to_value(&score).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "score": 9_912_345,
+                "lamp": "MAXXIVE CLEAR",
+                "matchType": "sdvxInGameID",
+                "identifier": "1500",
+                "difficulty": "MXM",
+                "timeAchieved": 1234,
+                "judgements": { "critical": 1150, "near": 40, "miss": 10 },
+                "hitMeta": {
+                    "fast": 25,
+                    "slow": 15,
+                    "maxCombo": 1200,
+                    "exScore": 2345,
+                    "gauge": 84.5,
+                },
+                "scoreMeta": { "inSkillAnalyser": true, "gauge": "EXCESSIVE" },
+            })
+        );
+    }
+
+    #[test]
+    fn import_score_leaves_out_unknown_values() {
+        let track = Track {
+            ex_score: 0,
+            gauge_type: 9,
+            ..track()
+        };
+        let score = ImportScore::from_track(GameVersion::ExceedGear, &track, 1234, None);
+        let json = serde_json::to_value(&score).unwrap();
+
+        // Clear type 4 is ULTIMATE CHAIN on Exceed Gear
+        assert_eq!(json["lamp"], "ULTIMATE CHAIN");
+        assert_eq!(json["hitMeta"]["exScore"], serde_json::Value::Null);
+        assert_eq!(json["scoreMeta"], serde_json::json!({}));
+    }
+
+    #[test]
+    fn only_refusals_are_given_up_on() {
+        for err in [
+            TachiError::Unauthorized(401),
+            TachiError::Unauthorized(403),
+            TachiError::MalformedBody(String::new()),
+        ] {
+            assert!(err.is_retryable() && err.needs_fixing(), "{err:?}");
+        }
+        for err in [
+            TachiError::Unreachable(String::new()),
+            TachiError::RateLimited(None),
+            TachiError::Server(502),
+        ] {
+            assert!(err.is_retryable() && !err.needs_fixing(), "{err:?}");
+        }
+        assert!(!TachiError::Rejected(String::new()).is_retryable());
+    }
+
+    #[test]
+    fn skill_level_names_match_serde() {
+        for level in 1..=12 {
+            let class = SkillLevel::from(level);
+            assert_eq!(serde_json::to_value(class).unwrap(), class.name());
+        }
+    }
+}
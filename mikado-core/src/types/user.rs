@@ -0,0 +1,100 @@
+use anyhow::Result;
+use url::Url;
+
+/// Name of the target made from the `api_key` set directly on a profile or in `[tachi]`.
+pub const DEFAULT_TARGET: &str = "default";
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    /// Every Tachi instance the scores of this profile are sent to.
+    pub targets: Vec<Target>,
+    /// Index of the target used to identify the player and read PBs from.
+    pub primary: usize,
+}
+
+impl Profile {
+    pub fn new(name: String, targets: Vec<Target>, primary: Option<&str>) -> Result<Self> {
+        if targets.is_empty() {
+            return Err(anyhow::anyhow!("Profile \"{name}\" has no Tachi target"));
+        }
+
+        let primary = match primary {
+            Some(primary) => targets
+                .iter()
+                .position(|target| target.name == primary)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Profile \"{name}\" has no target named \"{primary}\"")
+                })?,
+            None => 0,
+        };
+
+        Ok(Self {
+            name,
+            targets,
+            primary,
+        })
+    }
+
+    pub fn primary(&self) -> &Target {
+        &self.targets[self.primary]
+    }
+
+    pub fn target(&self, name: &str) -> Option<&Target> {
+        self.targets.iter().find(|target| target.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub name: String,
+    pub api_key: String,
+    pub urls: TachiUrls,
+}
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub tachi_id: u64,
+    pub card_id: String,
+    pub profile: Profile,
+    pub session_started: chrono::DateTime<chrono::Local>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TachiUrls {
+    pub status: String,
+    pub import: String,
+    pub pbs: String,
+    pub rivals: String,
+}
+
+impl TachiUrls {
+    pub fn new(
+        base_url: &str,
+        status: &str,
+        import: &str,
+        pbs: &str,
+        rivals: &str,
+    ) -> Result<Self> {
+        let base_url = Url::parse(base_url)
+            .map_err(|err| anyhow::anyhow!("Could not parse Tachi base URL: {err:#}"))?;
+        let join = |path: &str, name: &str| {
+            base_url
+                .join(path)
+                .map_err(|err| anyhow::anyhow!("Could not parse Tachi {name} URL: {err:#}"))
+        };
+
+        Ok(Self {
+            status: join(status, "status")?.to_string(),
+            import: join(import, "import")?.to_string(),
+            pbs: join(pbs, "PBs")?
+                .to_string()
+                .replace("%7B", "{")
+                .replace("%7D", "}"),
+            rivals: join(rivals, "rivals")?
+                .to_string()
+                .replace("%7B", "{")
+                .replace("%7D", "}"),
+        })
+    }
+}
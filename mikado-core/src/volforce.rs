@@ -0,0 +1,298 @@
+use crate::cloudlink::Pb;
+use crate::types::GameVersion;
+use crate::types::cloudlink::Chart;
+use crate::types::game::Track;
+use crate::types::tachi::TachiLamp;
+use log::{debug, error, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How many charts count towards the Volforce.
+const TOP_CHARTS: usize = 50;
+
+/// Best Volforce of each chart of the card being played, seeded from its Tachi PBs.
+static VOLFORCE: Mutex<Option<VolforceTable>> = Mutex::new(None);
+
+struct VolforceTable {
+    card_id: String,
+    charts: HashMap<Chart, ChartVolforce>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChartVolforce {
+    level: f64,
+    /// In thousandths, like the game truncates it.
+    best: u32,
+}
+
+/// Whether the Volforce coefficients of the version are known.
+///
+/// Those of Nabla have not been checked against the game yet, no Volforce is shown there rather
+/// than a wrong one.
+fn is_supported(version: GameVersion) -> bool {
+    match version {
+        GameVersion::ExceedGear => true,
+        GameVersion::Nabla => false,
+    }
+}
+
+/// Volforce of a single play in thousandths, `None` if the version is not supported.
+pub fn chart_volforce(
+    version: GameVersion,
+    level: f64,
+    score: u32,
+    lamp: TachiLamp,
+) -> Option<u32> {
+    if !is_supported(version) {
+        return None;
+    }
+    let grade = grade_coefficient(score);
+    let clear = lamp_coefficient(lamp);
+
+    // The epsilon keeps exact results like 340.0 from being truncated to 339
+    Some((level * (score as f64 / 10_000_000.0) * grade * clear * 20.0 + 1e-9).floor() as u32)
+}
+
+/// Volforce of the best plays, in thousandths.
+pub fn total(charts: impl IntoIterator<Item = u32>) -> u32 {
+    let mut charts = charts.into_iter().collect::<Vec<_>>();
+    charts.sort_unstable_by(|a, b| b.cmp(a));
+    charts.iter().take(TOP_CHARTS).sum()
+}
+
+fn grade_coefficient(score: u32) -> f64 {
+    match score {
+        9_900_000.. => 1.05,
+        9_800_000.. => 1.02,
+        9_700_000.. => 1.00,
+        9_500_000.. => 0.97,
+        9_300_000.. => 0.94,
+        9_000_000.. => 0.91,
+        8_700_000.. => 0.88,
+        7_500_000.. => 0.85,
+        6_500_000.. => 0.82,
+        _ => 0.80,
+    }
+}
+
+fn lamp_coefficient(lamp: TachiLamp) -> f64 {
+    match lamp {
+        TachiLamp::PerfectUltimateChain => 1.10,
+        TachiLamp::UltimateChain => 1.05,
+        TachiLamp::MaxxiveClear => 1.04,
+        TachiLamp::ExcessiveClear => 1.02,
+        TachiLamp::Clear => 1.00,
+        TachiLamp::Failed => 0.50,
+    }
+}
+
+/// Replaces the table with the Tachi PBs of the card, charts without a level are left out.
+pub(crate) fn seed(card_id: &str, version: GameVersion, pbs: &[Pb]) {
+    let table = if is_supported(version) {
+        let charts = pbs
+            .iter()
+            .filter_map(|pb| {
+                let level = pb.level?;
+                let lamp = TachiLamp::from_clear_type(version, pb.lamp);
+                let best = chart_volforce(version, level, pb.score, lamp)?;
+                Some((pb.chart, ChartVolforce { level, best }))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let volforce = total(charts.values().map(|chart| chart.best));
+        debug!("Volforce from Tachi PBs is {}", format_volforce(volforce));
+
+        Some(VolforceTable {
+            card_id: card_id.to_string(),
+            charts,
+        })
+    } else {
+        info!(
+            "Volforce is not shown on {} yet, its coefficients are not known",
+            version.display_name()
+        );
+        None
+    };
+
+    match VOLFORCE.lock() {
+        Ok(mut volforce) => *volforce = table,
+        Err(err) => error!("Volforce Mutex is poisoned: {err:#}"),
+    }
+}
+
+/// Adds the tracks to the table and logs how the Volforce changed.
+///
+/// Charts that were never played before are left out, their level only comes with the PBs. They
+/// count from the next time the card is scanned, once Tachi has a PB for them.
+pub fn update(card_id: &str, version: GameVersion, tracks: &[Track]) {
+    let mut table = match VOLFORCE.lock() {
+        Ok(table) => table,
+        Err(err) => {
+            error!("Volforce Mutex is poisoned: {err:#}");
+            return;
+        }
+    };
+    let Some(table) = table.as_mut().filter(|table| table.card_id == card_id) else {
+        return;
+    };
+
+    let before = total(table.charts.values().map(|chart| chart.best));
+    let mut new_charts = 0;
+    for track in tracks {
+        let chart = Chart {
+            song_id: track.music_id,
+            difficulty: track.music_type as u8,
+        };
+        let Some(chart) = table.charts.get_mut(&chart) else {
+            new_charts += 1;
+            continue;
+        };
+
+        let lamp = TachiLamp::from_clear_type(version, track.clear_type);
+        if let Some(volforce) = chart_volforce(version, chart.level, track.score, lamp) {
+            chart.best = chart.best.max(volforce);
+        }
+    }
+    // Only the best play of each chart is kept so it can't go down
+    let after = total(table.charts.values().map(|chart| chart.best));
+
+    if new_charts == 0 {
+        info!(
+            "VF {} → {} (+{})",
+            format_volforce(before),
+            format_volforce(after),
+            format_volforce(after - before)
+        );
+    } else {
+        info!(
+            "VF {} → {} (+{}), not counting {new_charts} chart(s) played for the first time",
+            format_volforce(before),
+            format_volforce(after),
+            format_volforce(after - before)
+        );
+    }
+}
+
+fn format_volforce(thousandths: u32) -> String {
+    format!("{}.{:03}", thousandths / 1000, thousandths % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn exceed_gear_maximum() {
+        // The best a chart can give, 0.462, so that 50 of them make the 23.100 Volforce cap
+        let best = chart_volforce(
+            GameVersion::ExceedGear,
+            20.0,
+            10_000_000,
+            TachiLamp::PerfectUltimateChain,
+        );
+        assert_eq!(best, Some(462));
+        assert_eq!(total([462; 50]), 23_100);
+    }
+
+    /// Level, score, lamp and Volforce in thousandths, worked out by hand from the grade and clear
+    /// coefficient tables rather than read from the game.
+    const WORKED_EXAMPLES: [(f64, u32, TachiLamp, u32); 3] = [
+        // 19 × 0.99 × 1.05 × 1.04 × 20 = 410.8104
+        (19.0, 9_900_000, TachiLamp::MaxxiveClear, 410),
+        // 18 × 0.995 × 1.05 × 1.05 × 20 = 394.9155
+        (18.0, 9_950_000, TachiLamp::UltimateChain, 394),
+        // 17 × 0.9 × 0.91 × 0.5 × 20 = 139.23
+        (17.0, 9_000_000, TachiLamp::Failed, 139),
+    ];
+
+    #[test]
+    fn exceed_gear_worked_examples() {
+        for (level, score, lamp, expected) in WORKED_EXAMPLES {
+            assert_eq!(
+                chart_volforce(GameVersion::ExceedGear, level, score, lamp),
+                Some(expected),
+                "{lamp:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn nabla_is_not_estimated() {
+        assert_eq!(
+            chart_volforce(GameVersion::Nabla, 20.0, 10_000_000, TachiLamp::Clear),
+            None
+        );
+    }
+
+    #[test]
+    fn exact_results_are_not_truncated() {
+        // 15 × 0.5125 × 0.80 × 20 is 123, but 122.99999999999999 with floats
+        let version = GameVersion::ExceedGear;
+        assert_eq!(
+            chart_volforce(version, 15.0, 5_125_000, TachiLamp::Clear),
+            Some(123)
+        );
+        assert_eq!(
+            chart_volforce(version, 16.0, 8_125_000, TachiLamp::Clear),
+            Some(221)
+        );
+    }
+
+    #[test]
+    fn total_counts_the_best_50_charts() {
+        assert_eq!(total((1..=60).rev()), (11..=60).sum::<u32>());
+        assert_eq!(total([]), 0);
+    }
+
+    fn total_of(card_id: &str) -> Option<u32> {
+        let table = VOLFORCE.lock().unwrap();
+        table
+            .as_ref()
+            .filter(|table| table.card_id == card_id)
+            .map(|table| total(table.charts.values().map(|chart| chart.best)))
+    }
+
+    #[test]
+    fn update_keeps_the_best_play_and_leaves_out_new_charts() {
+        let _guard = testing::isolate();
+        let version = GameVersion::ExceedGear;
+        let chart = Chart {
+            song_id: 1500,
+            difficulty: 3,
+        };
+        let pb = Pb {
+            chart,
+            level: Some(18.0),
+            score: 9_500_000,
+            lamp: TachiLamp::Clear.to_index(version),
+            grade: 7,
+            ex_score: 0,
+        };
+        let pbs = [pb];
+        seed(testing::CARD_ID, version, &pbs);
+        // 18 × 0.95 × 0.97 × 20
+        assert_eq!(total_of(testing::CARD_ID), Some(331));
+
+        let play = |music_id, score, clear_type| Track {
+            music_id,
+            music_type: 3,
+            score,
+            clear_type,
+            ..Default::default()
+        };
+        update(
+            testing::CARD_ID,
+            version,
+            &[play(1500, 9_000_000, 2), play(1600, 10_000_000, 5)],
+        );
+        assert_eq!(total_of(testing::CARD_ID), Some(331));
+
+        // 18 × 0.99 × 1.05 × 1.02 × 20
+        update(testing::CARD_ID, version, &[play(1500, 9_900_000, 3)]);
+        assert_eq!(total_of(testing::CARD_ID), Some(381));
+
+        seed(testing::CARD_ID, GameVersion::Nabla, &pbs);
+        assert_eq!(total_of(testing::CARD_ID), None);
+    }
+}
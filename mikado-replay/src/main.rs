@@ -0,0 +1,294 @@
+use anyhow::Result;
+use bytes::Bytes;
+use log::{error, info, warn};
+use mikado_core::configuration::{
+    ActiveConfiguration, CONFIGURATION_PATH, Configuration, HistoryExport,
+};
+use mikado_core::handlers::{save, scores};
+use mikado_core::property::{MemoryProperty, PropertyApi};
+use mikado_core::types::GameVersion;
+use mikado_core::types::game::{Game, Property};
+use mikado_core::{capture, helpers, history, queue};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: mikado-replay [OPTIONS] <DUMP>
+       mikado-replay export [--dir <GAME_DIR>] [FORMAT]...
+
+Builds the Tachi import of a captured call/game property and prints it.
+DUMP is the property as JSON, kbin or XML.
+
+export writes the score history of the game folder in each FORMAT, csv or batch-manual
+[default: csv], like history_export does when the game exits. It can run while the game is open.
+
+Options:
+  --version <exceed|nabla>  Game version, read from the method of kbin and XML dumps by default
+  --post                    Send the import to Tachi
+  --config <PATH>           Configuration holding the Tachi URL and key [default: mikado.toml]
+  --card <CARD>             Send to the primary target of this card's profile instead of [tachi]
+  --time <TIME>             When the play happened, as RFC 3339 or milliseconds since the Unix epoch
+                            [default: read from the name of captures, the current time otherwise]
+  --dir <GAME_DIR>          Folder holding mikado.history.jsonl [default: current folder]
+  -h, --help                Print this message";
+
+enum Command {
+    Replay(Arguments),
+    Export {
+        dir: Option<PathBuf>,
+        formats: Vec<HistoryExport>,
+    },
+}
+
+impl Command {
+    fn parse() -> Result<Self> {
+        let mut args = std::env::args().skip(1).peekable();
+        if args.peek().map(String::as_str) != Some("export") {
+            return Arguments::parse(args).map(Self::Replay);
+        }
+        args.next();
+
+        let mut dir = None;
+        let mut formats = vec![];
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dir" => {
+                    dir = Some(PathBuf::from(
+                        args.next()
+                            .ok_or_else(|| anyhow::anyhow!("{arg} expects a value"))?,
+                    ))
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(anyhow::anyhow!("Unknown option '{arg}'"));
+                }
+                _ => formats.push(
+                    serde_json::from_value(serde_json::Value::String(arg.clone()))
+                        .map_err(|_| anyhow::anyhow!("Unknown export format '{arg}'"))?,
+                ),
+            }
+        }
+        if formats.is_empty() {
+            formats.push(HistoryExport::Csv);
+        }
+
+        Ok(Self::Export { dir, formats })
+    }
+}
+
+struct Arguments {
+    dump: PathBuf,
+    version: Option<GameVersion>,
+    post: bool,
+    config: PathBuf,
+    card: Option<String>,
+    time: Option<u128>,
+}
+
+impl Arguments {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut dump = None;
+        let mut version = None;
+        let mut post = false;
+        let mut config = PathBuf::from(CONFIGURATION_PATH);
+        let mut card = None;
+        let mut time = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow::anyhow!("{arg} expects a value"))
+            };
+            match arg.as_str() {
+                "--version" => {
+                    let id = value()?;
+                    version = Some(
+                        GameVersion::from_tachi_id(&id)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown game version '{id}'"))?,
+                    );
+                }
+                "--post" => post = true,
+                "--config" => config = PathBuf::from(value()?),
+                "--card" => card = Some(value()?),
+                "--time" => time = Some(parse_time(&value()?)?),
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(anyhow::anyhow!("Unknown option '{arg}'"));
+                }
+                _ if dump.is_none() => dump = Some(PathBuf::from(arg)),
+                _ => return Err(anyhow::anyhow!("Unexpected argument '{arg}'")),
+            }
+        }
+
+        Ok(Self {
+            dump: dump.ok_or_else(|| anyhow::anyhow!("Missing the dump to replay"))?,
+            version,
+            post,
+            config,
+            card,
+            time,
+        })
+    }
+}
+
+fn parse_time(time: &str) -> Result<u128> {
+    if let Ok(millis) = time.parse() {
+        return Ok(millis);
+    }
+
+    chrono::DateTime::parse_from_rfc3339(time)
+        .map(|time| time.timestamp_millis() as u128)
+        .map_err(|err| anyhow::anyhow!("Could not parse time '{time}': {err}"))
+}
+
+/// The property as JSON and the method it was sent with, when the dump keeps it.
+fn read_dump(dump: &Path) -> Result<(String, Option<String>)> {
+    let content = std::fs::read(dump)
+        .map_err(|err| anyhow::anyhow!("Could not read {}: {err}", dump.display()))?;
+
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(&content) {
+        return Ok((json.to_string(), None));
+    }
+
+    let root = kbinxml::from_bytes(Bytes::from(content))
+        .and_then(|(collection, _)| collection.as_node())
+        .map_err(|err| anyhow::anyhow!("{} is not JSON, kbin or XML: {err}", dump.display()))?;
+    let property = MemoryProperty::new(root);
+    let method = property.call_attribute("method");
+
+    Ok((property.to_json()?, method))
+}
+
+fn run(arguments: Arguments) -> Result<()> {
+    let (json, method) = read_dump(&arguments.dump)?;
+    let version = arguments
+        .version
+        .or_else(|| method.as_deref().and_then(GameVersion::from_method))
+        .ok_or_else(|| anyhow::anyhow!("Could not tell the game version, pass --version"))?;
+    info!(
+        "Replaying {} as {}",
+        arguments.dump.display(),
+        version.display_name()
+    );
+
+    let property = serde_json::from_str::<Property>(&json)
+        .map_err(|err| anyhow::anyhow!("Could not parse property: {err:#}"))?;
+    let captured_at = arguments
+        .dump
+        .file_name()
+        .and_then(|name| capture::time_from_name(&name.to_string_lossy()));
+    let seen_at = match arguments.time.or(captured_at) {
+        Some(seen_at) => seen_at,
+        None => {
+            warn!("Could not tell when the play happened, using the current time, pass --time");
+            std::time::UNIX_EPOCH
+                .elapsed()
+                .map(|duration| duration.as_millis())
+                .unwrap_or_default()
+        }
+    };
+    let import = match property.call.game {
+        Game::Scores(game_scores) => scores::build_import(version, game_scores, seen_at),
+        Game::Save(game_save) => save::build_import(version, &game_save),
+        Game::Course(_) => {
+            return Err(anyhow::anyhow!(
+                "Skill analyzer courses are not sent to Tachi, replay the save_m of the run instead"
+            ));
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&import)?);
+
+    if !arguments.post {
+        return Ok(());
+    }
+
+    // Installed before any request so that the HTTP client never creates a default mikado.toml
+    let configuration = Configuration::load_path(&arguments.config)
+        .and_then(ActiveConfiguration::new)
+        .map_err(|err| err.context(format!("in {}", arguments.config.display())))?;
+    mikado_core::set_configuration(configuration);
+    let configuration = mikado_core::configuration();
+
+    let (recipient, url, api_key) = match &arguments.card {
+        Some(card) => {
+            let profile = helpers::get_profile(card)
+                .ok_or_else(|| anyhow::anyhow!("No profile for card {card}"))?;
+            let target = profile.primary().clone();
+            (
+                format!("profile \"{}\"", profile.name),
+                target.urls.import,
+                target.api_key,
+            )
+        }
+        None => {
+            let api_key = configuration
+                .tachi
+                .api_key
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("[tachi] has no api_key, pass --card"))?;
+            (
+                "[tachi]".to_string(),
+                configuration.default_tachi_urls.import.clone(),
+                api_key,
+            )
+        }
+    };
+
+    info!("Sending import to {url}");
+    match helpers::import_to_tachi(&url, &api_key, &import)? {
+        Some(document) => queue::report(&recipient, &document),
+        None => warn!("Tachi accepted the import but has not processed it yet"),
+    }
+
+    Ok(())
+}
+
+/// Exports the history the hook recorded, the files are written next to it.
+fn export(dir: Option<PathBuf>, formats: &[HistoryExport]) -> Result<()> {
+    if let Some(dir) = dir {
+        std::env::set_current_dir(&dir)
+            .map_err(|err| anyhow::anyhow!("Could not open {}: {err}", dir.display()))?;
+    }
+    if !Path::new(history::HISTORY_PATH).exists() {
+        return Err(anyhow::anyhow!(
+            "Could not find {}, run from the game folder or pass --dir",
+            history::HISTORY_PATH
+        ));
+    }
+
+    history::export(formats)
+}
+
+fn main() {
+    env_logger::builder()
+        .filter_level(log::LevelFilter::Info)
+        .parse_default_env()
+        .format(|f, record| {
+            use std::io::Write;
+
+            let message = mikado_core::redact::redacted(record.args().to_string());
+            writeln!(f, "[{}] {message}", record.level())
+        })
+        .init();
+
+    let command = match Command::parse() {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{err:#}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let result = match command {
+        Command::Replay(arguments) => run(arguments),
+        Command::Export { dir, formats } => export(dir, &formats),
+    };
+    if let Err(err) = result {
+        error!("{err:#}");
+        std::process::exit(1);
+    }
+}
@@ -1,2 +0,0 @@
-pub mod save;
-pub mod scores;
@@ -1,108 +1,17 @@
-mod cloudlink;
-mod configuration;
-mod handlers;
-mod helpers;
 mod log;
 mod mikado;
+mod property;
 mod sys;
-mod types;
-
-use std::collections::HashMap;
 
 use crate::log::Logger;
 use crate::mikado::{hook_init, hook_release};
-use crate::types::user::Profile;
-use ::log::{error, info, warn};
-use configuration::Configuration;
-use std::sync::LazyLock;
-use url::Url;
+use ::log::{error, info};
+use mikado_core::helpers;
 use windows::Win32::Foundation::{HINSTANCE, TRUE};
 use windows::Win32::System::Console::AllocConsole;
 use windows::Win32::System::SystemServices::{DLL_PROCESS_ATTACH, DLL_PROCESS_DETACH};
 use windows::core::BOOL;
 
-pub static CONFIGURATION: LazyLock<Configuration> = LazyLock::new(|| {
-    let result = Configuration::load();
-    if let Err(err) = result {
-        error!("{err:#}");
-        std::process::exit(1);
-    }
-
-    result.unwrap()
-});
-
-pub static CARD_PROFILES: LazyLock<HashMap<String, Profile>> = LazyLock::new(|| {
-    let mut cards: HashMap<String, Profile> = HashMap::new();
-
-    for (profile_name, profile_config) in &CONFIGURATION.profiles {
-        for card in &profile_config.cards {
-            if let Some(cards_config) = &CONFIGURATION.cards
-                && !cards_config.whitelist.is_empty()
-                && cards_config.whitelist.contains(card)
-            {
-                warn!(
-                    "Card {} is in the default [cards] whitelist and also assigned to profile \"{}\". The profile assignment will be ignored. Remove it from the [cards] whitelist if you want it to use the profile.",
-                    card, profile_name
-                );
-                continue;
-            }
-            if let Some(existing_profile) = cards.get(card.as_str()) {
-                warn!(
-                    "Card {} is already assigned to profile \"{}\" but appears again in profile \"{}\". Ignoring.",
-                    card, existing_profile.name, profile_name
-                );
-                continue;
-            }
-            cards.insert(
-                card.to_string(),
-                Profile {
-                    name: profile_name.clone(),
-                    api_key: profile_config.api_key.clone(),
-                },
-            );
-        }
-    }
-
-    cards
-});
-
-pub static TACHI_STATUS_URL: LazyLock<String> = LazyLock::new(|| {
-    let result = Url::parse(&CONFIGURATION.tachi.base_url)
-        .and_then(|url| url.join(&CONFIGURATION.tachi.status));
-    if let Err(err) = result {
-        error!("Could not parse Tachi status URL: {err:#}");
-        std::process::exit(1);
-    }
-
-    result.unwrap().to_string()
-});
-
-pub static TACHI_IMPORT_URL: LazyLock<String> = LazyLock::new(|| {
-    let result = Url::parse(&CONFIGURATION.tachi.base_url)
-        .and_then(|url| url.join(&CONFIGURATION.tachi.import));
-    if let Err(err) = result {
-        error!("Could not parse Tachi import URL: {err:#}");
-        std::process::exit(1);
-    }
-
-    result.unwrap().to_string()
-});
-
-pub static TACHI_PBS_URL: LazyLock<String> = LazyLock::new(|| {
-    let result = Url::parse(&CONFIGURATION.tachi.base_url)
-        .and_then(|url| url.join(&CONFIGURATION.tachi.pbs));
-    if let Err(err) = result {
-        error!("Could not parse Tachi PBS URL: {err:#}");
-        std::process::exit(1);
-    }
-
-    result
-        .unwrap()
-        .to_string()
-        .replace("%7B", "{")
-        .replace("%7D", "}")
-});
-
 fn print_infos() {
     info!(
         "Starting Mikado v{}-{} by adamaq01",
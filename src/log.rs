@@ -57,7 +57,9 @@ impl Logger {
 
                 let time = chrono::Local::now().format("%d/%m/%Y %H:%M:%S");
 
-                writeln!(f, "[{time}] {level} {target} -> {}", record.args())
+                let message = mikado_core::redact::redacted(record.args().to_string());
+
+                writeln!(f, "[{time}] {level} {target} -> {message}")
             })
             .init();
     }
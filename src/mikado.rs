@@ -1,32 +1,32 @@
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{OnceLock, RwLock};
-
 use anyhow::Result;
-use bytes::Bytes;
-use kbinxml::{CompressionType, EncodingType, Node, Options, Value};
 use log::{debug, error, info, warn};
-
-use crate::handlers::save::process_save;
-use crate::handlers::scores::process_scores;
-use crate::sys::{
-    NodeType, property_clear_error, property_mem_write, property_node_name, property_node_refer,
-    property_query_size, property_search, property_set_flag,
-};
-use crate::types::GameProperties;
-use crate::types::game::Property;
-use crate::types::user::User;
-use crate::{CONFIGURATION, TACHI_STATUS_URL, helpers};
-
-pub static CURRENT_USER: RwLock<Option<User>> = RwLock::new(None);
-pub static GAME_PROPERTIES: OnceLock<GameProperties> = OnceLock::new();
+use mikado_core::configuration::SubmissionMode;
+use mikado_core::hook::{self, GAME_PROPERTIES};
+use mikado_core::types::GameProperties;
+
+use crate::property::{Avs2Property, read_node_str};
+
+unsafe fn read_game_properties(node: *const ()) -> Option<GameProperties> {
+    let read =
+        |path: &std::ffi::CStr, length| unsafe { read_node_str(node, path.as_ptr(), length) };
+
+    Some(GameProperties::new(
+        read(c"/soft/model", 3)?,
+        read(c"/soft/dest", 1)?,
+        read(c"/soft/spec", 1)?,
+        read(c"/soft/rev", 1)?,
+        read(c"/soft/ext", 10)?.parse().unwrap_or(0),
+    ))
+}
 
 pub fn hook_init(ea3_node: *const ()) -> Result<()> {
-    if !CONFIGURATION.general.enable {
+    let configuration = mikado_core::configuration();
+    if !configuration.general.enable {
         return Ok(());
     }
 
     let game_properties = {
-        let properties = unsafe { GameProperties::from_ea3_node(ea3_node) };
+        let properties = unsafe { read_game_properties(ea3_node) };
         if properties.is_none() {
             warn!("Could not read game version, hook might not work properly");
         }
@@ -49,19 +49,30 @@ pub fn hook_init(ea3_node: *const ()) -> Result<()> {
     // Initializing function detours
     crochet::enable!(property_destroy_hook)
         .map_err(|err| anyhow::anyhow!("Could not enable function detour: {:#}", err))?;
-    if CONFIGURATION.general.inject_cloud_pbs {
-        debug!("PBs injection enabled");
+    let live = configuration.general.submission == SubmissionMode::Live;
+    let inject =
+        (configuration.general.inject_cloud_pbs || configuration.general.inject_rivals) && live;
+    if inject {
+        debug!("Cloud injection enabled");
+    }
+    if configuration.general.capture {
+        info!("Capture enabled, e-amusement traffic will be written to mikado-capture");
+    }
+    if inject || configuration.general.capture {
         crochet::enable!(property_mem_read_hook)
             .map_err(|err| anyhow::anyhow!("Could not enable function detour: {:#}", err))?;
     }
 
+    hook::start();
+
     info!("Hook successfully initialized");
 
     Ok(())
 }
 
 pub fn hook_release() -> Result<()> {
-    if !CONFIGURATION.general.enable {
+    let configuration = mikado_core::configuration();
+    if !configuration.general.enable {
         return Ok(());
     }
 
@@ -75,13 +86,11 @@ pub fn hook_release() -> Result<()> {
             .map_err(|err| anyhow::anyhow!("Could not disable function detour: {:#}", err))?;
     }
 
+    hook::stop();
+
     Ok(())
 }
 
-static LOAD: AtomicBool = AtomicBool::new(false);
-static LOAD_M: AtomicBool = AtomicBool::new(false);
-static COMMON: AtomicBool = AtomicBool::new(false);
-
 #[crochet::hook("avs2-core.dll", "XCgsqzn00000b7")]
 pub unsafe fn property_mem_read_hook(
     ptr: *const (),
@@ -91,15 +100,12 @@ pub unsafe fn property_mem_read_hook(
     size: u32,
 ) -> *const () {
     unsafe {
-        let load = LOAD.load(Ordering::SeqCst);
-        let load_m = LOAD_M.load(Ordering::SeqCst);
-        let common = COMMON.load(Ordering::SeqCst);
-        if !load && !load_m && !common {
+        if !hook::awaiting_response() {
             return call_original!(ptr, something, flags, data, size);
         }
 
         let bytes = std::slice::from_raw_parts(ptr as *const u8, something as usize).to_vec();
-        match property_mem_read_hook_wrapped(bytes, load, load_m, common) {
+        match hook::process_response(bytes) {
             Some(Ok(response)) => {
                 call_original!(
                     response.as_ptr() as *const (),
@@ -118,104 +124,6 @@ pub unsafe fn property_mem_read_hook(
     }
 }
 
-fn build_response(
-    original_signature: &[u8],
-    response: Node,
-    encoding: EncodingType,
-) -> Result<Vec<u8>> {
-    if kbinxml::is_binary_xml(original_signature) {
-        let bytes = kbinxml::to_binary_with_options(
-            Options::new(CompressionType::from_byte(original_signature[1])?, encoding),
-            &response,
-        )?;
-        Ok(bytes)
-    } else {
-        let bytes = kbinxml::to_text_xml(&response)?;
-        Ok(bytes)
-    }
-}
-
-#[allow(clippy::manual_map)]
-pub unsafe fn property_mem_read_hook_wrapped(
-    original: Vec<u8>,
-    load: bool,
-    load_m: bool,
-    common: bool,
-) -> Option<Result<Vec<u8>>> {
-    let original_signature = original[..2].to_vec();
-    let (mut root, encoding) = kbinxml::from_bytes(Bytes::from(original))
-        .and_then(|(node, encoding)| node.as_node().map(|node| (node, encoding)))
-        .ok()?;
-
-    if common
-        .then(|| root.pointer(&["game", "event"]))
-        .flatten()
-        .is_some()
-    {
-        Some((|| {
-            let events = root
-                .pointer_mut(&["game", "event"])
-                .expect("Could not find events node");
-
-            events.children_mut().retain(|info| {
-                if let Some(Value::String(event_id)) = info
-                    .pointer(&["event_id"])
-                    .and_then(|event_id| event_id.value())
-                {
-                    event_id != "CLOUD_LINK_ENABLE"
-                } else {
-                    true
-                }
-            });
-            events.children_mut().push(Node::with_nodes(
-                "info",
-                vec![Node::with_value(
-                    "event_id",
-                    Value::String("CLOUD_LINK_ENABLE".to_string()),
-                )],
-            ));
-            let response = build_response(&original_signature, root, encoding)?;
-            COMMON.store(false, Ordering::Relaxed);
-
-            Ok(response)
-        })())
-    } else if helpers::get_current_user().is_some()
-        && load
-            .then(|| root.pointer(&["game", "code"]))
-            .flatten()
-            .is_some()
-    {
-        Some((|| {
-            let game = root
-                .pointer_mut(&["game"])
-                .expect("Could not find game node");
-            game.children_mut().retain(|node| node.key() != "cloud");
-            game.children_mut().push(Node::with_nodes(
-                "cloud",
-                vec![Node::with_value("relation", Value::S8(1))],
-            ));
-            let response = build_response(&original_signature, root, encoding)?;
-            LOAD.store(false, Ordering::Relaxed);
-
-            Ok(response)
-        })())
-    } else if let Some(music) = load_m.then(|| root.pointer(&["game", "music"])).flatten() {
-        if let Some(user) = helpers::get_current_user() {
-            Some((|| {
-                let response = crate::cloudlink::process_pbs(&user, music)?;
-                let response = build_response(&original_signature, response, encoding)?;
-                LOAD_M.store(false, Ordering::Relaxed);
-
-                Ok(response)
-            })())
-        } else {
-            None
-        }
-    } else {
-        None
-    }
-}
-
 #[crochet::hook("avs2-core.dll", "XCgsqzn0000091")]
 pub unsafe fn property_destroy_hook(property: *mut ()) -> i32 {
     unsafe {
@@ -223,211 +131,12 @@ pub unsafe fn property_destroy_hook(property: *mut ()) -> i32 {
             return 0;
         }
 
-        let node = property_search(property, std::ptr::null(), b"/call/game\0".as_ptr());
-        let node = if node.is_null() {
-            property_search(property, std::ptr::null(), b"/call/cardmng\0".as_ptr())
-        } else {
-            node
-        };
-        if node.is_null() {
-            property_clear_error(property);
-            return call_original!(property);
-        }
-
-        let mut buffer = [0u8; 256];
-        let result = property_node_name(node, buffer.as_mut_ptr(), buffer.len() as u32);
-        if result < 0 {
-            return call_original!(property);
-        }
-
-        let name = {
-            let result = std::str::from_utf8(&buffer[0..32]);
-            if let Err(err) = result {
-                error!("Could not convert buffer to string: {err:#}");
-                return call_original!(property);
-            }
-
-            result.unwrap().replace('\0', "")
-        };
-        if name != "game" && name != "cardmng" {
-            return call_original!(property);
-        }
-
-        let result = property_node_refer(
-            property,
-            node,
-            b"method@\0".as_ptr(),
-            NodeType::NodeAttr,
-            buffer.as_mut_ptr() as *mut (),
-            256,
-        );
-        if result < 0 {
-            return call_original!(property);
-        }
-
-        let method = {
-            let result = std::str::from_utf8(&buffer[0..11]);
-            if let Err(err) = result {
-                error!("Could not convert buffer to string: {err:#}");
-                return call_original!(property);
-            }
-
-            result.unwrap().replace('\0', "")
-        };
-        debug!("Intercepted '{name}' method: {method}");
-
-        if name == "cardmng" {
-            if method != "inquire" {
-                return call_original!(property);
-            }
-
-            let result = property_node_refer(
-                property,
-                node,
-                b"cardid@\0".as_ptr(),
-                NodeType::NodeAttr,
-                buffer.as_mut_ptr() as *mut (),
-                256,
-            );
-            if result < 0 {
-                return call_original!(property);
-            }
-
-            let card_id = {
-                let result = std::str::from_utf8(&buffer[..32]);
-                if let Err(err) = result {
-                    error!("Could not convert buffer to string: {err:#}");
-                    return call_original!(property);
-                }
-
-                result.unwrap().replace('\0', "")
-            };
-
-            let profile = helpers::get_profile(&card_id);
-            if profile.is_none() {
-                warn!("No profile for card {card_id}");
-            }
-
-            // Try to reach Tachi API
-            fn get_tachi_user(key: impl AsRef<str>) -> Result<u64> {
-                let response: serde_json::Value =
-                    helpers::request_tachi("GET", TACHI_STATUS_URL.as_str(), key, None::<()>)?;
-
-                response["body"]["whoami"]
-                    .as_u64()
-                    .ok_or_else(|| anyhow::anyhow!("Couldn't parse user from Tachi response"))
-            }
-
-            let tachi_id =
-                profile
-                    .as_ref()
-                    .and_then(|profile| match get_tachi_user(&profile.api_key) {
-                        Ok(user) => {
-                            debug!("Tachi API reached, set current user to {user}");
-                            Some(user)
-                        }
-                        Err(e) => {
-                            warn!("did not set Tachi user: {e}");
-                            None
-                        }
-                    });
-
-            if let Ok(mut guard) = CURRENT_USER.write() {
-                *guard = tachi_id.and_then(|tachi_id| {
-                    profile.map(|profile| {
-                        info!(
-                            "Setting current profile to \"{}\": card is {}, tachi is {}",
-                            &profile.name, card_id, tachi_id
-                        );
-                        User {
-                            tachi_id,
-                            card_id,
-                            profile,
-                        }
-                    })
-                });
-            } else {
-                warn!("Could not acquire write lock on current user");
-            }
-
-            return call_original!(property);
-        }
-
-        let prefix = GAME_PROPERTIES
-            .get()
-            .map(|p| p.version().method_prefix())
-            .unwrap_or("sv6");
-        let method = method
-            .strip_prefix(prefix)
-            .and_then(|s| s.strip_prefix('_'))
-            .unwrap_or("");
-
-        if CONFIGURATION.general.inject_cloud_pbs {
-            if method == "load_m" {
-                LOAD_M.store(true, Ordering::Relaxed);
-            } else if method == "common" {
-                COMMON.store(true, Ordering::Relaxed);
-            } else if method == "load" {
-                LOAD.store(true, Ordering::Relaxed);
-            }
-        }
-
-        if method != "save_m" && (!CONFIGURATION.general.export_class || method != "save") {
-            return call_original!(property);
-        }
-
-        property_set_flag(property, 0x800, 0x008);
-
-        let size = property_query_size(property);
-        if size < 0 {
-            property_set_flag(property, 0x008, 0x800);
-            return call_original!(property);
-        }
-
-        let buffer = vec![0u8; size as usize];
-        let result = property_mem_write(property, buffer.as_ptr() as *mut u8, buffer.len() as u32);
-        property_set_flag(property, 0x008, 0x800);
-        if result < 0 {
-            return call_original!(property);
-        }
-
-        // Read buf to string
-        let property_str = {
-            let result = std::str::from_utf8(&buffer);
-            if let Err(err) = result {
-                error!("Could not convert buffer to string: {err:#}");
-                return call_original!(property);
-            }
-
-            result.unwrap()
-        };
-
-        debug!("Processing property: {property_str}");
-        if let Err(err) =
-            match method {
-                "save_m" => {
-                    serde_json::from_str::<Property>(property_str)
-                        .map_err(|err| anyhow::anyhow!("Could not parse property: {err:#}"))
-                        .and_then(|prop| {
-                            process_scores(prop.call.game.left().ok_or_else(|| {
-                                anyhow::anyhow!("Could not process scores property")
-                            })?)
-                        })
-                }
-                "save" => {
-                    serde_json::from_str::<Property>(property_str)
-                        .map_err(|err| anyhow::anyhow!("Could not parse property: {err:#}"))
-                        .and_then(|prop| {
-                            process_save(prop.call.game.right().ok_or_else(|| {
-                                anyhow::anyhow!("Could not process save property")
-                            })?)
-                        })
-                }
-                _ => unreachable!(),
-            }
-        {
-            error!("{err:#}");
-        }
+        // The game does not say when the chart was played, this is the closest we can get
+        let seen_at = std::time::UNIX_EPOCH
+            .elapsed()
+            .map(|duration| duration.as_millis())
+            .unwrap_or_default();
+        hook::handle_property(&Avs2Property::new(property), seen_at);
 
         call_original!(property)
     }
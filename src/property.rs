@@ -0,0 +1,140 @@
+use crate::sys::{
+    NodeType, property_clear_error, property_mem_write, property_node_name, property_node_refer,
+    property_query_size, property_search, property_set_flag,
+};
+use anyhow::Result;
+use mikado_core::property::PropertyApi;
+use std::ffi::c_char;
+
+const CALL_PATHS: [&[u8]; 2] = [b"/call/game\0", b"/call/cardmng\0"];
+
+/// A property owned by the game.
+pub struct Avs2Property {
+    property: *mut (),
+    node: *mut (),
+}
+
+impl Avs2Property {
+    /// # Safety
+    ///
+    /// `property` must be a valid avs2 property for as long as the returned value is used.
+    pub unsafe fn new(property: *mut ()) -> Self {
+        let node = CALL_PATHS
+            .iter()
+            .map(|path| unsafe { property_search(property, std::ptr::null(), path.as_ptr()) })
+            .find(|node| !node.is_null())
+            .unwrap_or_else(|| {
+                // Searching for a missing node leaves the property in an error state
+                unsafe { property_clear_error(property) };
+                std::ptr::null_mut()
+            });
+
+        Self { property, node }
+    }
+
+    /// Writes the property in the format of its current flags.
+    fn write(&self) -> Result<Vec<u8>> {
+        unsafe {
+            let size = property_query_size(self.property);
+            if size < 0 {
+                return Err(anyhow::anyhow!("Could not query property size"));
+            }
+
+            let buffer = vec![0u8; size as usize];
+            let result = property_mem_write(
+                self.property,
+                buffer.as_ptr() as *mut u8,
+                buffer.len() as u32,
+            );
+            if result < 0 {
+                return Err(anyhow::anyhow!("Could not write property to memory"));
+            }
+
+            Ok(buffer)
+        }
+    }
+
+    fn read_buffer(buffer: &[u8]) -> Option<String> {
+        let end = buffer
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(buffer.len());
+        std::str::from_utf8(&buffer[..end]).map(str::to_string).ok()
+    }
+}
+
+impl PropertyApi for Avs2Property {
+    fn call_name(&self) -> Option<String> {
+        if self.node.is_null() {
+            return None;
+        }
+
+        let mut buffer = [0u8; 256];
+        let result =
+            unsafe { property_node_name(self.node, buffer.as_mut_ptr(), buffer.len() as u32) };
+        if result < 0 {
+            return None;
+        }
+
+        Self::read_buffer(&buffer)
+    }
+
+    fn call_attribute(&self, name: &str) -> Option<String> {
+        if self.node.is_null() {
+            return None;
+        }
+
+        let path = format!("{name}@\0");
+        let mut buffer = [0u8; 256];
+        let result = unsafe {
+            property_node_refer(
+                self.property,
+                self.node,
+                path.as_ptr(),
+                NodeType::NodeAttr,
+                buffer.as_mut_ptr() as *mut (),
+                buffer.len() as u32,
+            )
+        };
+        if result < 0 {
+            return None;
+        }
+
+        Self::read_buffer(&buffer)
+    }
+
+    fn to_json(&self) -> Result<String> {
+        unsafe {
+            property_set_flag(self.property, 0x800, 0x008);
+            let buffer = self.write();
+            property_set_flag(self.property, 0x008, 0x800);
+
+            String::from_utf8(buffer?)
+                .map_err(|err| anyhow::anyhow!("Could not convert buffer to string: {err:#}"))
+        }
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        self.write()
+    }
+}
+
+pub unsafe fn read_node_str(node: *const (), path: *const c_char, length: usize) -> Option<String> {
+    let mut buffer = [0u8; 32];
+    let result = unsafe {
+        property_node_refer(
+            node,
+            node,
+            path as _,
+            NodeType::NodeStr,
+            buffer.as_mut_ptr() as *mut (),
+            32,
+        )
+    };
+
+    if result < 0 {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&buffer[..length]).to_string())
+}